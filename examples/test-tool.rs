@@ -35,7 +35,7 @@ fn run(args: &mut env::Args, map: &mut MapMut, file: &File) {
                 if let Some(_) = guard {
                     error!("lock is already held");
                 } else {
-                    let g = file_guard::lock(file, lock, off, len)
+                    let g = file_guard::lock(file, lock, off as u64, len as u64)
                         .unwrap_or_else(|e| error!("{:?} lock failed: {}", lock, e));
                     println!("{} {:?}", arg, lock);
                     guard = Some(g);
@@ -46,7 +46,7 @@ fn run(args: &mut env::Args, map: &mut MapMut, file: &File) {
                 if let Some(_) = guard {
                     error!("lock is already held");
                 } else {
-                    match file_guard::try_lock(file, lock, off, len) {
+                    match file_guard::try_lock(file, lock, off as u64, len as u64) {
                         Err(e) => {
                             if e.kind() == ErrorKind::WouldBlock {
                                 println!("{} None", arg)
@@ -61,12 +61,44 @@ fn run(args: &mut env::Args, map: &mut MapMut, file: &File) {
                     }
                 }
             }
+            "+locktimeout" => {
+                let (lock, off, len) = lock_values(args, map.len());
+                let ms = int(args, 0, usize::MAX) as u64;
+                if let Some(_) = guard {
+                    error!("lock is already held");
+                } else {
+                    let timeout = time::Duration::from_millis(ms);
+                    match file_guard::lock_timeout(file, lock, off as u64, len as u64, timeout) {
+                        Err(e) => {
+                            if e.kind() == ErrorKind::TimedOut {
+                                println!("{} None", arg)
+                            } else {
+                                error!("{:?} timed lock failed: {}", lock, e)
+                            }
+                        }
+                        Ok(g) => {
+                            println!("{} {:?}", arg, lock);
+                            guard = Some(g);
+                        }
+                    }
+                }
+            }
+            "+probe" => {
+                let (lock, off, len) = lock_values(args, map.len());
+                match file_guard::probe(file, lock, off as u64, len as u64) {
+                    Err(e) => error!("{:?} probe failed: {}", lock, e),
+                    Ok(None) => println!("{} None", arg),
+                    Ok(Some(c)) => {
+                        println!("{} {:?} {} {}", arg, c.lock, c.range.start, c.range.end)
+                    }
+                }
+            }
             "+lockany" => {
                 let (off, len) = lock_size(args, map.len());
                 if let Some(_) = guard {
                     error!("lock is already held");
                 } else {
-                    let g = file_guard::lock_any(file, off, len)
+                    let g = file_guard::lock_any(file, off as u64, len as u64)
                         .unwrap_or_else(|e| error!("any lock failed: {}", e));
                     println!("{} {:?}", arg, g.lock_type());
                     guard = Some(g);
@@ -0,0 +1,66 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+const MIN_DELAY: Duration = Duration::from_millis(1);
+const MAX_DELAY: Duration = Duration::from_millis(50);
+
+/// An exponential delay used when polling for a lock.
+#[derive(Clone, Debug)]
+pub(crate) struct Backoff {
+    delay: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    pub(crate) fn new() -> Self {
+        Self::with_delays(MIN_DELAY, MAX_DELAY)
+    }
+
+    /// Creates a backoff from `min` to `max`, where delays shorter than
+    /// `MIN_DELAY` are raised to it so that polling never spins.
+    pub(crate) fn with_delays(min: Duration, max: Duration) -> Self {
+        let max = max.max(MIN_DELAY);
+        Backoff {
+            delay: min.clamp(MIN_DELAY, max),
+            max,
+        }
+    }
+
+    /// Gets the next delay to wait, doubling the delay that follows it.
+    pub(crate) fn next_delay(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = self.delay.saturating_mul(2).min(self.max);
+        delay
+    }
+
+    /// Gets the next delay to wait, but not beyond `deadline`.
+    ///
+    /// Returns `None` if the deadline has already passed.
+    pub(crate) fn delay_until(&mut self, deadline: Option<Instant>) -> Option<Duration> {
+        let delay = self.next_delay();
+        match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    None
+                } else {
+                    Some(delay.min(deadline - now))
+                }
+            }
+            None => Some(delay),
+        }
+    }
+
+    /// Sleeps for the next delay, but not beyond `deadline`.
+    ///
+    /// Returns `false` without sleeping if the deadline has already passed.
+    pub(crate) fn sleep_until(&mut self, deadline: Option<Instant>) -> bool {
+        match self.delay_until(deadline) {
+            Some(delay) => {
+                thread::sleep(delay);
+                true
+            }
+            None => false,
+        }
+    }
+}
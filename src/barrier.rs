@@ -0,0 +1,212 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+use crate::backoff::Backoff;
+use crate::os::{raw_read_at, raw_write_at};
+use crate::{Backend, FileGuard, Lock};
+
+/// The length of the generation, which is followed by the arrival slots.
+const GENERATION_LEN: u64 = 8;
+
+/// A reusable barrier that blocks processes until enough of them have
+/// reached it.
+///
+/// The 8 bytes at `offset` hold the generation of the barrier as a
+/// little-endian `u64`, and are followed by two banks of `n` arrival slots,
+/// one byte each. Each caller of [`wait()`] claims an [`Exclusive`] lock of
+/// the generation, and probes the slots of the bank for the current
+/// generation to count the processes already waiting. The caller that
+/// brings the count up to `n` increments the generation, releasing the
+/// barrier. The others claim an [`Exclusive`] lock of a free slot and poll
+/// for the generation to change. As each use of the barrier has its own
+/// generation, and alternates between the banks, the barrier may be waited
+/// on again as soon as it releases.
+///
+/// Arrivals are only counted through the locks of the slots, which the
+/// system releases when a process exits, so a process that exits while
+/// waiting is no longer counted. Every process must wait with the same `n`.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::FileBarrier;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-barrier")?;
+///
+/// let barrier = FileBarrier::new(&file, 0);
+/// // a barrier of one process releases immediately
+/// assert!(barrier.wait(1)?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`wait()`]: #method.wait
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+pub struct FileBarrier<T: Deref<Target = File>> {
+    file: T,
+    offset: u64,
+    backend: Backend,
+}
+
+impl<T> FileBarrier<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Creates a new barrier using the bytes of a file at `offset`.
+    ///
+    /// A barrier of `n` processes uses `8 + 2 * n` bytes. The file must be
+    /// opened for both reading and writing. The bytes do not need to exist in
+    /// the underlying file.
+    pub fn new(file: T, offset: u64) -> Self {
+        Self::with_backend(Backend::Default, file, offset)
+    }
+
+    /// Creates a new barrier using the locks of a [`Backend`].
+    ///
+    /// [`Backend`]: enum.Backend.html
+    pub fn with_backend(backend: Backend, file: T, offset: u64) -> Self {
+        FileBarrier {
+            file,
+            offset,
+            backend,
+        }
+    }
+
+    /// Waits until `n` processes, including this one, have reached the
+    /// barrier.
+    ///
+    /// Returns `true` for the single caller that released the barrier, and
+    /// `false` for the others. If `n` is zero, or the slots would be beyond
+    /// the largest offset, an `Error` of kind `ErrorKind::InvalidInput` is
+    /// returned.
+    pub fn wait(&self, n: u64) -> io::Result<bool> {
+        self.wait_until(n, None)
+    }
+
+    /// Waits up to `timeout` for `n` processes to reach the barrier.
+    ///
+    /// If the barrier is not released before the timeout elapses, this
+    /// process is no longer counted and an `Error` of kind
+    /// `ErrorKind::TimedOut` is returned. A timeout too large to ever elapse
+    /// waits as [`wait()`] does.
+    ///
+    /// [`wait()`]: #method.wait
+    pub fn wait_timeout(&self, n: u64, timeout: Duration) -> io::Result<bool> {
+        self.wait_until(n, Instant::now().checked_add(timeout))
+    }
+
+    /// Waits until `deadline` for `n` processes to reach the barrier.
+    ///
+    /// If the barrier is not released before the deadline passes, this
+    /// process is no longer counted and an `Error` of kind
+    /// `ErrorKind::TimedOut` is returned.
+    pub fn wait_deadline(&self, n: u64, deadline: Instant) -> io::Result<bool> {
+        self.wait_until(n, Some(deadline))
+    }
+
+    /// Gets a reference to the underlying file.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.file
+    }
+
+    /// Consumes the barrier, returning the underlying file.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.file
+    }
+
+    fn wait_until(&self, n: u64, deadline: Option<Instant>) -> io::Result<bool> {
+        let end = n
+            .checked_mul(2)
+            .and_then(|len| len.checked_add(GENERATION_LEN))
+            .and_then(|len| self.offset.checked_add(len));
+        if n == 0 || end.is_none() {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        let (generation, slot) = {
+            let guard = self.lock(Lock::Exclusive)?;
+            let generation = self.read(&guard)?;
+            let bank = self.offset + GENERATION_LEN + (generation % 2) * n;
+
+            let (mut arrived, mut free) = (1, None);
+            for slot in bank..bank + n {
+                match self.backend.probe(&self.file, Lock::Exclusive, slot, 1)? {
+                    Some(_) => arrived += 1,
+                    None => {
+                        free.get_or_insert(slot);
+                    }
+                }
+            }
+            match free {
+                Some(slot) if arrived < n => {
+                    let slot = self
+                        .backend
+                        .try_lock(&*self.file, Lock::Exclusive, slot, 1)?;
+                    (generation, slot)
+                }
+                _ => {
+                    self.write(&guard, generation.wrapping_add(1))?;
+                    return Ok(true);
+                }
+            }
+        };
+
+        let mut backoff = Backoff::new();
+        loop {
+            if self.read(&self.lock(Lock::Shared)?)? != generation {
+                return Ok(false);
+            }
+            if !backoff.sleep_until(deadline) {
+                break;
+            }
+        }
+
+        // The barrier may have been released since it was last checked, and
+        // the slot must not be released while another process is counting.
+        let guard = self.lock(Lock::Exclusive)?;
+        if self.read(&guard)? != generation {
+            return Ok(false);
+        }
+        drop(slot);
+        Err(ErrorKind::TimedOut.into())
+    }
+
+    fn lock(&self, lock: Lock) -> io::Result<FileGuard<&File>> {
+        self.backend
+            .lock(&*self.file, lock, self.offset, GENERATION_LEN)
+    }
+
+    /// Reads the generation through a guard of its bytes.
+    fn read(&self, guard: &FileGuard<&File>) -> io::Result<u64> {
+        let mut buf = [0; GENERATION_LEN as usize];
+        raw_read_at(guard, &mut buf, self.offset)?;
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    /// Writes the generation through an [`Exclusive`] guard of its bytes.
+    ///
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    fn write(&self, guard: &FileGuard<&File>, generation: u64) -> io::Result<()> {
+        raw_write_at(guard, &generation.to_le_bytes(), self.offset)
+    }
+}
+
+impl<T> fmt::Debug for FileBarrier<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileBarrier({})", self.offset)
+    }
+}
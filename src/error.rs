@@ -0,0 +1,325 @@
+use std::fs::File;
+use std::ops::Deref;
+use std::{error, fmt, io};
+
+use crate::FileGuard;
+
+/// An error returned by [`.unlock()`] when the lock could not be released.
+///
+/// This combines the error that happened while unlocking with the original
+/// [`FileGuard`], which still holds the lock, so the unlock may be retried.
+/// Converting an `UnlockError` into an `io::Error` drops the guard, which
+/// attempts to unlock once more.
+///
+/// [`.unlock()`]: struct.FileGuard.html#method.unlock
+/// [`FileGuard`]: struct.FileGuard.html
+pub struct UnlockError<T: Deref<Target = File>> {
+    guard: FileGuard<T>,
+    error: io::Error,
+}
+
+impl<T> UnlockError<T>
+where
+    T: Deref<Target = File>,
+{
+    pub(crate) fn new(guard: FileGuard<T>, error: io::Error) -> Self {
+        UnlockError { guard, error }
+    }
+
+    /// Gets the error that happened while unlocking.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Gets the guard that failed to unlock.
+    pub fn guard(&self) -> &FileGuard<T> {
+        &self.guard
+    }
+
+    /// Returns the guard that failed to unlock, discarding the error.
+    pub fn into_guard(self) -> FileGuard<T> {
+        self.guard
+    }
+
+    /// Returns both the error and the guard that failed to unlock.
+    pub fn into_parts(self) -> (io::Error, FileGuard<T>) {
+        (self.error, self.guard)
+    }
+}
+
+impl<T> From<UnlockError<T>> for io::Error
+where
+    T: Deref<Target = File>,
+{
+    fn from(err: UnlockError<T>) -> io::Error {
+        err.error
+    }
+}
+
+impl<T> fmt::Debug for UnlockError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnlockError")
+            .field("guard", &self.guard)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for UnlockError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<T> error::Error for UnlockError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An error returned by [`.split_at()`] when the lock could not be split.
+///
+/// This combines the error that happened while splitting with the original
+/// [`FileGuard`], which still holds its whole lock. Converting a
+/// `SplitError` into an `io::Error` drops the guard, releasing its lock.
+///
+/// [`.split_at()`]: struct.FileGuard.html#method.split_at
+/// [`FileGuard`]: struct.FileGuard.html
+pub struct SplitError<T: Deref<Target = File>> {
+    guard: FileGuard<T>,
+    error: io::Error,
+}
+
+impl<T> SplitError<T>
+where
+    T: Deref<Target = File>,
+{
+    pub(crate) fn new(guard: FileGuard<T>, error: io::Error) -> Self {
+        SplitError { guard, error }
+    }
+
+    /// Gets the error that happened while splitting.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Gets the guard that failed to split.
+    pub fn guard(&self) -> &FileGuard<T> {
+        &self.guard
+    }
+
+    /// Returns the guard that failed to split, discarding the error.
+    pub fn into_guard(self) -> FileGuard<T> {
+        self.guard
+    }
+
+    /// Returns both the error and the guard that failed to split.
+    pub fn into_parts(self) -> (io::Error, FileGuard<T>) {
+        (self.error, self.guard)
+    }
+}
+
+impl<T> From<SplitError<T>> for io::Error
+where
+    T: Deref<Target = File>,
+{
+    fn from(err: SplitError<T>) -> io::Error {
+        err.error
+    }
+}
+
+impl<T> fmt::Debug for SplitError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SplitError")
+            .field("guard", &self.guard)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for SplitError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<T> error::Error for SplitError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An error returned by [`FileGuard::merge()`] when two locks could not be
+/// merged.
+///
+/// This combines the error that happened while merging with both of the
+/// original guards, in the order they were given, each still holding its
+/// lock. Converting a `MergeError` into an `io::Error` drops both guards,
+/// releasing their locks.
+///
+/// [`FileGuard::merge()`]: struct.FileGuard.html#method.merge
+pub struct MergeError<T: Deref<Target = File>> {
+    guards: (FileGuard<T>, FileGuard<T>),
+    error: io::Error,
+}
+
+impl<T> MergeError<T>
+where
+    T: Deref<Target = File>,
+{
+    pub(crate) fn new(a: FileGuard<T>, b: FileGuard<T>, error: io::Error) -> Self {
+        MergeError {
+            guards: (a, b),
+            error,
+        }
+    }
+
+    /// Gets the error that happened while merging.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Gets the guards that failed to merge.
+    pub fn guards(&self) -> (&FileGuard<T>, &FileGuard<T>) {
+        (&self.guards.0, &self.guards.1)
+    }
+
+    /// Returns the guards that failed to merge, discarding the error.
+    pub fn into_guards(self) -> (FileGuard<T>, FileGuard<T>) {
+        self.guards
+    }
+
+    /// Returns both the error and the guards that failed to merge.
+    pub fn into_parts(self) -> (io::Error, FileGuard<T>, FileGuard<T>) {
+        (self.error, self.guards.0, self.guards.1)
+    }
+}
+
+impl<T> From<MergeError<T>> for io::Error
+where
+    T: Deref<Target = File>,
+{
+    fn from(err: MergeError<T>) -> io::Error {
+        err.error
+    }
+}
+
+impl<T> fmt::Debug for MergeError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MergeError")
+            .field("guards", &self.guards)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for MergeError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<T> error::Error for MergeError<T>
+where
+    T: Deref<Target = File>,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// An error returned by [`upgrade_with_retry()`] and the upgrades of a
+/// [`FlockGuard`] when a lock could not be upgraded.
+///
+/// This combines the error that happened while upgrading with the original
+/// guard of type `G`, which still holds its [`Shared`] lock. If the lock was
+/// released during the upgrade and could not be claimed again, the guard no
+/// longer holds any lock, so it is dropped and no guard is returned.
+///
+/// [`upgrade_with_retry()`]: os/unix/trait.FileGuardExt.html#tymethod.upgrade_with_retry
+/// [`FlockGuard`]: os/unix/flock/struct.FlockGuard.html
+/// [`Shared`]: enum.Lock.html#variant.Shared
+pub struct UpgradeError<G> {
+    guard: Option<G>,
+    error: io::Error,
+}
+
+impl<G> UpgradeError<G> {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    pub(crate) fn new(guard: Option<G>, error: io::Error) -> Self {
+        UpgradeError { guard, error }
+    }
+
+    /// Gets the error that happened while upgrading.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// Gets the guard that failed to upgrade, if it still holds its lock.
+    pub fn guard(&self) -> Option<&G> {
+        self.guard.as_ref()
+    }
+
+    /// Returns the guard that failed to upgrade, if it still holds its lock,
+    /// discarding the error.
+    pub fn into_guard(self) -> Option<G> {
+        self.guard
+    }
+
+    /// Returns both the error and the guard that failed to upgrade, if it
+    /// still holds its lock.
+    pub fn into_parts(self) -> (io::Error, Option<G>) {
+        (self.error, self.guard)
+    }
+}
+
+impl<G> From<UpgradeError<G>> for io::Error {
+    fn from(err: UpgradeError<G>) -> io::Error {
+        err.error
+    }
+}
+
+impl<G: fmt::Debug> fmt::Debug for UpgradeError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UpgradeError")
+            .field("guard", &self.guard)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<G> fmt::Display for UpgradeError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<G: fmt::Debug> error::Error for UpgradeError<G> {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
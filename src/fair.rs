@@ -0,0 +1,113 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+
+use crate::os::raw_backend_lock;
+use crate::{next_lock_id, Backend, FileGuard, Lock, Wait};
+
+/// Gets the offset of the pending byte of a byte range, which follows the
+/// intent byte used by an [`UpgradableGuard`].
+///
+/// [`UpgradableGuard`]: struct.UpgradableGuard.html
+pub(crate) fn pending_offset(offset: u64, len: u64) -> io::Result<u64> {
+    match offset.checked_add(len).and_then(|end| end.checked_add(1)) {
+        Some(pending) if len > 0 && pending < u64::MAX => Ok(pending),
+        _ => Err(ErrorKind::InvalidInput.into()),
+    }
+}
+
+/// Claims a lock of a byte range through its pending byte.
+///
+/// A [`Shared`] lock first claims the pending byte [`Shared`], so it waits
+/// while a writer holds the pending byte. An [`Exclusive`] lock claims the
+/// pending byte [`Exclusive`] for as long as it waits for the byte range,
+/// holding back any new readers. It first claims the intent byte, as an
+/// [`UpgradableGuard`] does, so that it waits for an upgrading guard rather
+/// than holding the pending byte that the upgrade needs. The intent and
+/// pending bytes are released once the byte range is locked.
+///
+/// [`Shared`]: enum.Lock.html#variant.Shared
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+/// [`UpgradableGuard`]: struct.UpgradableGuard.html
+pub(crate) fn acquire<T>(
+    backend: Backend,
+    file: T,
+    lock: Lock,
+    offset: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<FileGuard<T>>
+where
+    T: Deref<Target = File>,
+{
+    let (pending, id) = (pending_offset(offset, len)?, next_lock_id());
+    let intent = match lock {
+        Lock::Exclusive => Some((pending - 1, next_lock_id())),
+        Lock::Shared => None,
+    };
+    let unlock_intent = || unsafe {
+        if let Some((off, id)) = intent {
+            let _ = raw_backend_lock(&file, backend, id, None, off, 1, false);
+        }
+    };
+
+    unsafe {
+        if let Some((off, id)) = intent {
+            raw_backend_lock(&file, backend, id, Some(Lock::Exclusive), off, 1, wait)?;
+        }
+        if let Err(e) = raw_backend_lock(&file, backend, id, Some(lock), pending, 1, wait) {
+            unlock_intent();
+            return Err(e);
+        }
+    }
+
+    let options = backend.options(
+        Some(lock),
+        offset,
+        len,
+        if wait { Wait::Block } else { Wait::Try },
+    );
+    // The lock is handed over to a guard that owns the file.
+    let result = options.acquire(&*file).map(FileGuard::into_id);
+    unsafe {
+        let _ = raw_backend_lock(&file, backend, id, None, pending, 1, false);
+    }
+    unlock_intent();
+    result.map(|id| options.guard(file, lock, id))
+}
+
+/// Exchanges the held [`Shared`] lock with the id `id` of a byte range for
+/// an [`Exclusive`] one, holding the pending byte while waiting so no new
+/// readers are admitted.
+///
+/// The [`Shared`] lock is released before the [`Exclusive`] lock is claimed,
+/// so the caller must otherwise exclude other writers. If the [`Exclusive`]
+/// lock cannot be claimed, the [`Shared`] lock is claimed again, waiting even
+/// when `wait` is false, for any writer outside the protocol that locked the
+/// byte range in between.
+///
+/// [`Shared`]: enum.Lock.html#variant.Shared
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+pub(crate) unsafe fn raw_upgrade(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    offset: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<()> {
+    let (pending, pending_id) = (pending_offset(offset, len)?, next_lock_id());
+    let lock_pending =
+        |lock, wait| raw_backend_lock(f, backend, pending_id, lock, pending, 1, wait);
+    lock_pending(Some(Lock::Exclusive), wait)?;
+
+    let lock = |lock, wait| raw_backend_lock(f, backend, id, lock, offset, len, wait);
+    let result = lock(None, false).and_then(|_| match lock(Some(Lock::Exclusive), wait) {
+        // Readers never conflict with the shared lock, and writers that
+        // follow the protocol are held back, so this rarely waits.
+        Err(e) => lock(Some(Lock::Shared), true).and(Err(e)),
+        ok => ok,
+    });
+    let _ = lock_pending(None, false);
+    result
+}
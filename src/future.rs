@@ -0,0 +1,269 @@
+//! Provides runtime-agnostic asynchronous lock acquisition.
+//!
+//! The [`LockFuture`] returned by the functions in this module may be awaited
+//! on any executor, including a plain `block_on`. Rather than blocking one
+//! thread per waiting lock, each attempt to claim the lock is made without
+//! blocking while the future is polled, and a single shared waiter thread
+//! wakes the task once its next attempt is due.
+//!
+//! The futures are cancellation-safe: a lock is only ever claimed while the
+//! future is polled, so dropping a [`LockFuture`] before it completes never
+//! leaves a lock behind.
+//!
+//! This module requires the `future` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use file_guard::Lock;
+//! use std::fs::OpenOptions;
+//!
+//! # async fn example() -> std::io::Result<()> {
+//! let file = OpenOptions::new()
+//!     .read(true)
+//!     .write(true)
+//!     .create(true)
+//!     .open("example-lock")?;
+//!
+//! let lock = file_guard::future::lock_any(&file, 0, 1).await?;
+//! println!("claimed a {} lock", lock.lock_type());
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`LockFuture`]: struct.LockFuture.html
+use std::fs::File;
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::Instant;
+use std::{fmt, thread};
+
+use crate::backoff::Backoff;
+use crate::{next_lock_id, Backend, FileGuard, Lock, LockOptions, Wait};
+
+/// Wait and claim the desired [`Lock`] type using a byte range of a file.
+///
+/// The byte range does not need to exist in the underlying file.
+///
+/// [`Lock`]: ../enum.Lock.html
+pub fn lock<T: Deref<Target = File>>(file: T, lock: Lock, offset: u64, len: u64) -> LockFuture<T> {
+    acquire(
+        &Backend::Default.options(Some(lock), offset, len, Wait::Block),
+        file,
+    )
+}
+
+/// Wait and claim either an [`Exclusive`] or [`Shared`] lock for a byte range
+/// of a file.
+///
+/// When successful, the [`FileGuard`] may be inspected for the lock type
+/// obtained using [`.lock_type()`].
+///
+/// The byte range does not need to exist in the underlying file.
+///
+/// [`Exclusive`]: ../enum.Lock.html#variant.Exclusive
+/// [`Shared`]: ../enum.Lock.html#variant.Shared
+/// [`FileGuard`]: ../struct.FileGuard.html
+/// [`.lock_type()`]: ../struct.FileGuard.html#method.lock_type
+pub fn lock_any<T: Deref<Target = File>>(file: T, offset: u64, len: u64) -> LockFuture<T> {
+    acquire(
+        &Backend::Default.options(None, offset, len, Wait::Block),
+        file,
+    )
+}
+
+/// Claim a lock on `file` with the options specified by `options`.
+///
+/// The [`Wait`] strategy is respected, except that [`Wait::Block`] polls for
+/// the lock rather than blocking the thread.
+///
+/// [`Wait`]: ../enum.Wait.html
+/// [`Wait::Block`]: ../enum.Wait.html#variant.Block
+pub fn acquire<T: Deref<Target = File>>(options: &LockOptions, file: T) -> LockFuture<T> {
+    LockFuture {
+        options: options.clone(),
+        id: next_lock_id(),
+        polling: options.polling(),
+        file: Some(file),
+        entry: None,
+    }
+}
+
+/// A future that resolves to a [`FileGuard`] once the lock is claimed.
+///
+/// This structure is created by the [`lock()`], [`lock_any()`], and
+/// [`acquire()`] functions.
+///
+/// [`FileGuard`]: ../struct.FileGuard.html
+/// [`lock()`]: fn.lock.html
+/// [`lock_any()`]: fn.lock_any.html
+/// [`acquire()`]: fn.acquire.html
+#[must_use = "futures do nothing unless you `.await` or poll them"]
+pub struct LockFuture<T: Deref<Target = File>> {
+    options: LockOptions,
+    id: u64,
+    polling: Option<(Backoff, Option<Instant>)>,
+    file: Option<T>,
+    entry: Option<Arc<Entry>>,
+}
+
+// The file is never pinned, so moving it out of a pinned future is sound.
+impl<T: Deref<Target = File>> Unpin for LockFuture<T> {}
+
+impl<T> fmt::Debug for LockFuture<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LockFuture")
+            .field("options", &self.options)
+            .field("waiting", &self.entry.is_some())
+            .finish()
+    }
+}
+
+impl<T> Future for LockFuture<T>
+where
+    T: Deref<Target = File>,
+{
+    type Output = io::Result<FileGuard<T>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let file = this
+            .file
+            .as_ref()
+            .expect("LockFuture polled after completion");
+
+        if let Some(ref entry) = this.entry {
+            let mut state = entry.lock();
+            if !state.woken {
+                if !state.waker.will_wake(cx.waker()) {
+                    state.waker = cx.waker().clone();
+                }
+                return Poll::Pending;
+            }
+        }
+        this.entry = None;
+
+        let result = match this.options.attempt(file, this.id) {
+            Err(e) if e.kind() == ErrorKind::WouldBlock => match this.polling {
+                Some((ref mut backoff, deadline)) => match backoff.delay_until(deadline) {
+                    Some(delay) => {
+                        // A delay beyond any representable instant is never due.
+                        if let Some(at) = Instant::now().checked_add(delay) {
+                            this.entry = Some(waiter().push(at, cx.waker().clone()));
+                        }
+                        return Poll::Pending;
+                    }
+                    None => Err(ErrorKind::TimedOut.into()),
+                },
+                None => Err(e),
+            },
+            result => result,
+        };
+
+        let file = this.file.take().unwrap();
+        Poll::Ready(result.map(|lock| this.options.guard(file, lock, this.id)))
+    }
+}
+
+/// A pending wake-up of the task waiting for a [`LockFuture`].
+///
+/// The waiter thread never touches the file, which may be closed once the
+/// future is dropped or forgotten.
+struct Entry {
+    state: Mutex<EntryState>,
+}
+
+struct EntryState {
+    at: Instant,
+    waker: Waker,
+    woken: bool,
+}
+
+impl Entry {
+    fn lock(&self) -> MutexGuard<'_, EntryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Wakes the task if it is due, returning the time it is due otherwise,
+    /// or `None` once it has been woken.
+    fn poll(&self, now: Instant) -> Option<Instant> {
+        let mut state = self.lock();
+        if now < state.at {
+            return Some(state.at);
+        }
+
+        state.woken = true;
+        let waker = state.waker.clone();
+        drop(state);
+        waker.wake();
+        None
+    }
+}
+
+struct Waiter {
+    queue: Mutex<Vec<Arc<Entry>>>,
+    cond: Condvar,
+}
+
+fn waiter() -> &'static Waiter {
+    static WAITER: OnceLock<Waiter> = OnceLock::new();
+    WAITER.get_or_init(|| {
+        thread::Builder::new()
+            .name("file-guard-waiter".into())
+            .spawn(|| waiter().run())
+            .expect("failed to spawn the file-guard waiter thread");
+        Waiter {
+            queue: Mutex::new(Vec::new()),
+            cond: Condvar::new(),
+        }
+    })
+}
+
+impl Waiter {
+    fn push(&self, at: Instant, waker: Waker) -> Arc<Entry> {
+        let entry = Arc::new(Entry {
+            state: Mutex::new(EntryState {
+                at,
+                waker,
+                woken: false,
+            }),
+        });
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        queue.push(entry.clone());
+        self.cond.notify_one();
+        entry
+    }
+
+    fn run(&self) {
+        let mut queue = self.queue.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let now = Instant::now();
+            let mut next = None;
+            queue.retain(|entry| match entry.poll(now) {
+                Some(at) => {
+                    next = Some(next.map_or(at, |n: Instant| n.min(at)));
+                    true
+                }
+                None => false,
+            });
+
+            queue = match next {
+                Some(at) => {
+                    let delay = at.saturating_duration_since(Instant::now());
+                    self.cond
+                        .wait_timeout(queue, delay)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self.cond.wait(queue).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
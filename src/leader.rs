@@ -0,0 +1,179 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+
+use crate::os::{raw_read_at, raw_write_at};
+use crate::{Backend, FileGuard, Lock};
+
+/// The length of the fencing token, which is also the byte range locked by
+/// the leader.
+const TOKEN_LEN: u64 = 8;
+
+/// Elects a single leader among processes, with fencing tokens.
+///
+/// The leader holds an [`Exclusive`] lock on the 8 bytes at `offset`, which
+/// also store the fencing token as a little-endian `u64`. Each new leader
+/// increments the token and writes it back to the file before its
+/// [`Leadership`] is returned, so the token of every leader is greater than
+/// that of any previous leader. Downstream systems may remember the largest
+/// token they have seen and reject the writes of a stale leader that still
+/// believes it leads. Leadership is given up when the [`Leadership`] is
+/// dropped or the process of the leader exits.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::LeaderElection;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-leader")?;
+///
+/// let election = LeaderElection::new(&file, 0);
+/// let leader = election.wait_for_leadership()?;
+/// println!("leading with token {}", leader.token());
+/// assert!(election.is_leader_alive()?);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+/// [`Leadership`]: struct.Leadership.html
+pub struct LeaderElection<T: Deref<Target = File>> {
+    file: T,
+    offset: u64,
+    backend: Backend,
+}
+
+impl<T> LeaderElection<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Creates a new election using the 8 bytes of a file at `offset`.
+    ///
+    /// The file must be opened for both reading and writing. The bytes do not
+    /// need to exist in the underlying file, and are read as a token of zero
+    /// until the first leader is elected.
+    pub fn new(file: T, offset: u64) -> Self {
+        Self::with_backend(Backend::Default, file, offset)
+    }
+
+    /// Creates a new election using the locks of a [`Backend`].
+    ///
+    /// [`Backend`]: enum.Backend.html
+    pub fn with_backend(backend: Backend, file: T, offset: u64) -> Self {
+        LeaderElection {
+            file,
+            offset,
+            backend,
+        }
+    }
+
+    /// Waits until no other process leads and becomes the leader.
+    pub fn wait_for_leadership(&self) -> io::Result<Leadership<'_>> {
+        let guard = self
+            .backend
+            .lock(&*self.file, Lock::Exclusive, self.offset, TOKEN_LEN)?;
+        self.elect(guard)
+    }
+
+    /// Attempts to become the leader.
+    ///
+    /// If another process leads, an `Error` of kind `ErrorKind::WouldBlock` is
+    /// returned.
+    pub fn try_lead(&self) -> io::Result<Leadership<'_>> {
+        let guard = self
+            .backend
+            .try_lock(&*self.file, Lock::Exclusive, self.offset, TOKEN_LEN)?;
+        self.elect(guard)
+    }
+
+    /// Checks if any process currently leads, without blocking.
+    ///
+    /// This is checked with [`probe()`], so it is only a snapshot and may be
+    /// stale by the time it is used. With the [`Default`] backend, a leader
+    /// within this process is also reported.
+    ///
+    /// [`probe()`]: fn.probe.html
+    /// [`Default`]: enum.Backend.html#variant.Default
+    pub fn is_leader_alive(&self) -> io::Result<bool> {
+        let conflict = self
+            .backend
+            .probe(&self.file, Lock::Shared, self.offset, TOKEN_LEN)?;
+        Ok(conflict.is_some())
+    }
+
+    /// Gets a reference to the underlying file.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.file
+    }
+
+    /// Consumes the election, returning the underlying file.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.file
+    }
+
+    /// Increments the fencing token for a newly elected leader.
+    fn elect<'a>(&self, guard: FileGuard<&'a File>) -> io::Result<Leadership<'a>> {
+        let mut buf = [0; TOKEN_LEN as usize];
+        raw_read_at(&guard, &mut buf, self.offset)?;
+        let token = u64::from_le_bytes(buf)
+            .checked_add(1)
+            .ok_or(ErrorKind::InvalidData)?;
+        raw_write_at(&guard, &token.to_le_bytes(), self.offset)?;
+        guard.sync_data()?;
+        Ok(Leadership { guard, token })
+    }
+}
+
+impl<T> fmt::Debug for LeaderElection<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "LeaderElection({})", self.offset)
+    }
+}
+
+/// An RAII guard for the leadership of a [`LeaderElection`]. When dropped,
+/// leadership is given up.
+///
+/// This structure is created by the [`wait_for_leadership()`] and
+/// [`try_lead()`] methods.
+///
+/// [`LeaderElection`]: struct.LeaderElection.html
+/// [`wait_for_leadership()`]: struct.LeaderElection.html#method.wait_for_leadership
+/// [`try_lead()`]: struct.LeaderElection.html#method.try_lead
+#[must_use = "if unused leadership will immediately be given up"]
+pub struct Leadership<'a> {
+    guard: FileGuard<&'a File>,
+    token: u64,
+}
+
+impl<'a> Leadership<'a> {
+    /// Gets the fencing token of this leader, which is greater than the token
+    /// of every previous leader.
+    #[inline]
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// Gets the guard holding the leadership lock.
+    #[inline]
+    pub fn guard(&self) -> &FileGuard<&'a File> {
+        &self.guard
+    }
+}
+
+impl fmt::Debug for Leadership<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Leadership({})", self.token)
+    }
+}
@@ -8,10 +8,20 @@
 //! not need to exist within the file, and the ranges may be used for any
 //! arbitrary advisory locking protocol between processes.
 //!
-//! The result of a [`lock()`], [`try_lock()`], or [`lock_any()`] is a
-//! [`FileGuard`]. When dropped, this [`FileGuard`] will unlock the region of
-//! the file currently held. Exclusive locks may be [`.downgrade()`]'ed to
-//! either a shared lock cross platform.
+//! The result of a [`lock()`], [`try_lock()`], [`lock_file()`], or
+//! [`lock_any()`] is a [`FileGuard`]. When dropped, this [`FileGuard`] will
+//! unlock the region of the file currently held. Several ranges may be
+//! claimed together with [`lock_many()`], or across files with
+//! [`lock_all()`], which return a [`GuardSet`].
+//! Exclusive locks may be [`.downgrade()`]'ed to either a shared lock cross
+//! platform. For finer control over how a lock is acquired, such as waiting
+//! with a timeout, use [`LockOptions`]. To exclude the threads of the process
+//! as well as other processes, use a [`ProcessRwLock`]. To limit how many
+//! processes may proceed at once, use a [`FileSemaphore`], or to give each
+//! process a unique number, a [`SlotAllocator`]. A single leader may be elected
+//! among processes with a [`LeaderElection`], and a shared file initialized
+//! exactly once with a [`OnceFile`]. To hold processes until enough of them
+//! have reached the same point, use a [`FileBarrier`].
 //!
 //! On Unix systems `fcntl` is used to perform the locking, and on Windows, `LockFileEx`.
 //! All generally available behavior is consistent across platforms. For platform-
@@ -20,9 +30,26 @@
 //! be done safely and atomically. To use this feature, the
 //! [`file_guard::os::unix::FileGuardExt`] may `use`ed, enabling the [`.upgrade()`]
 //! and [`.try_upgrade()`] methods.
+//! An upgrade that is portable and cannot deadlock with other upgrades is
+//! provided by the [`UpgradableGuard`] from [`lock_upgradable()`], and
+//! [`lock_fair()`] keeps waiting writers from being starved by readers.
 //!
 //! Note that on Windows, the file must be open with write permissions to lock it.
 //!
+//! Byte ranges are given as `u64` offsets and lengths on every platform, so
+//! files larger than 4 GiB may be locked from 32-bit targets. A range whose
+//! end overflows, or on Unix systems exceeds the largest `off_t`, fails with
+//! an `Error` of kind `ErrorKind::InvalidInput`.
+//!
+//! The Unix `fcntl` locks are owned by the process, and closing any descriptor
+//! of a file releases all of the process's locks on it. The guards of the
+//! process are tracked in a registry keyed by device and inode, so overlapping
+//! guards exclude each other as they would between processes, and dropping or
+//! downgrading a guard never changes the bytes of another. On Linux, the
+//! [`Backend::OpenFile`] locks may be used instead, which are owned by the
+//! opened `File`. Whole-file `flock` locks, which interoperate with `flock(1)`,
+//! are provided by [`file_guard::os::unix::flock`].
+//!
 //! # Examples
 //!
 //! ```
@@ -103,23 +130,88 @@
 //! ```
 //!
 //! [`FileGuard`]: struct.FileGuard.html
+//! [`Backend::OpenFile`]: enum.Backend.html#variant.OpenFile
+//! [`LockOptions`]: struct.LockOptions.html
+//! [`ProcessRwLock`]: struct.ProcessRwLock.html
+//! [`FileSemaphore`]: struct.FileSemaphore.html
+//! [`SlotAllocator`]: struct.SlotAllocator.html
+//! [`LeaderElection`]: struct.LeaderElection.html
+//! [`OnceFile`]: struct.OnceFile.html
+//! [`FileBarrier`]: struct.FileBarrier.html
 //! [`lock()`]: fn.lock.html
 //! [`try_lock()`]: fn.try_lock.html
+//! [`lock_file()`]: fn.lock_file.html
 //! [`lock_any()`]: fn.lock_any.html
+//! [`lock_many()`]: fn.lock_many.html
+//! [`lock_all()`]: fn.lock_all.html
+//! [`lock_upgradable()`]: fn.lock_upgradable.html
+//! [`lock_fair()`]: fn.lock_fair.html
+//! [`UpgradableGuard`]: struct.UpgradableGuard.html
+//! [`GuardSet`]: struct.GuardSet.html
 //! [`.downgrade()`]: struct.FileGuard.html#method.downgrade
 //! [`file_guard::os::unix::FileGuardExt`]: os/unix/trait.FileGuardExt.html
+//! [`file_guard::os::unix::flock`]: os/unix/flock/index.html
 //! [`.upgrade()`]: os/unix/trait.FileGuardExt.html#tymethod.upgrade
 //! [`.try_upgrade()`]: os/unix/trait.FileGuardExt.html#tymethod.try_upgrade
 
 #![deny(missing_docs)]
 
 use std::fs::File;
-use std::io::ErrorKind;
-use std::ops::{Deref, DerefMut, Range};
-use std::{fmt, io};
+use std::mem::ManuallyDrop;
+use std::ops::{Bound, Deref, DerefMut, Range, RangeBounds};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use std::{fmt, io, ptr};
+
+mod backoff;
+
+mod barrier;
+pub use self::barrier::FileBarrier;
+
+mod options;
+pub use self::options::{LockOptions, Wait};
+
+mod leader;
+pub use self::leader::{LeaderElection, Leadership};
+
+mod once;
+pub use self::once::OnceFile;
+
+mod rwlock;
+pub use self::rwlock::{ProcessReadGuard, ProcessRwLock, ProcessWriteGuard};
+
+mod semaphore;
+pub use self::semaphore::{FileSemaphore, Permit};
+
+mod set;
+pub use self::set::GuardSet;
+
+mod fair;
+
+mod upgradable;
+pub use self::upgradable::UpgradableGuard;
+
+mod slot;
+pub use self::slot::{Slot, SlotAllocator};
+
+mod unlock;
+pub use self::unlock::{set_unlock_error_hook, take_unlock_error_hook, UnlockErrorHook};
+
+mod error;
+pub use self::error::{MergeError, SplitError, UnlockError, UpgradeError};
 
 pub mod os;
-use self::os::{raw_file_downgrade, raw_file_lock};
+
+#[cfg(feature = "future")]
+pub mod future;
+
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+use self::os::{
+    raw_backend_downgrade, raw_backend_extend, raw_backend_lock, raw_backend_merge,
+    raw_backend_probe, raw_backend_shrink, raw_backend_split,
+};
 
 /// The type of a lock operation.
 ///
@@ -130,7 +222,7 @@ use self::os::{raw_file_downgrade, raw_file_lock};
 /// [`lock()`]: fn.lock.html
 /// [`try_lock()`]: fn.try_lock.html
 /// [`lock_any()`]: fn.lock_any.html
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
 pub enum Lock {
     /// A shared lock may be concurrently held by multiple processes while
     /// preventing future exclusive locks its lifetime.
@@ -158,6 +250,403 @@ pub enum Lock {
     Exclusive,
 }
 
+impl fmt::Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Lock::Shared => "shared",
+            Lock::Exclusive => "exclusive",
+        })
+    }
+}
+
+/// A lock that prevents claiming a byte range of a file.
+///
+/// This is returned by [`probe()`] to describe the holder of a conflicting
+/// lock. Displaying a `Conflict` gives a summary suitable for logging, such
+/// as `pid 4123 (exclusive 0..1)`.
+///
+/// [`probe()`]: fn.probe.html
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Conflict {
+    /// The [`Lock`] type held.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    pub lock: Lock,
+    /// The byte range of the held lock. A lock extending to the end of the
+    /// file and beyond has a range ending at `u64::MAX`.
+    pub range: Range<u64>,
+    /// The id of the process holding the lock, when known.
+    ///
+    /// This is `None` for open file description locks and on Windows.
+    pub pid: Option<i32>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.pid {
+            Some(pid) => write!(f, "pid {}", pid)?,
+            None => f.write_str("unknown process")?,
+        }
+        write!(f, " ({} {:?})", self.lock, self.range)
+    }
+}
+
+/// The locking mechanism used to claim a byte range of a file.
+///
+/// The [`Default`] backend is used by [`lock()`], [`try_lock()`], and
+/// [`lock_any()`]. Other backends may be selected by calling the equivalent
+/// methods on the desired variant. The backend used is retained by the
+/// [`FileGuard`] so that later downgrades, upgrades, and the final unlock use
+/// the same mechanism.
+///
+/// [`Default`]: enum.Backend.html#variant.Default
+/// [`lock()`]: fn.lock.html
+/// [`try_lock()`]: fn.try_lock.html
+/// [`lock_any()`]: fn.lock_any.html
+/// [`FileGuard`]: struct.FileGuard.html
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Backend {
+    /// The standard locks of the platform.
+    ///
+    /// On Unix systems these are `fcntl` record locks. These locks are owned
+    /// by the process, and closing *any* file descriptor for the file releases
+    /// every lock the process holds on it. The kernel merges the locks of a
+    /// process, so the guards of this backend are also tracked in a
+    /// process-wide registry keyed by the device and inode of the file. A
+    /// guard that overlaps a conflicting guard of the same process waits for
+    /// it to be dropped, or fails with an `Error` of kind
+    /// `ErrorKind::WouldBlock` when not waiting. As with a `Mutex`, waiting on
+    /// a guard held by the same thread blocks forever, unless detected with
+    /// [`set_deadlock_detection()`]. Unlocking or downgrading a guard only
+    /// affects the bytes no other guard still holds. Locks taken directly
+    /// with `raw_file_lock` bypass the registry. On Windows these are
+    /// `LockFileEx` locks.
+    ///
+    /// [`set_deadlock_detection()`]: os/unix/fn.set_deadlock_detection.html
+    #[default]
+    Default,
+    /// Locks owned by the open file description.
+    ///
+    /// On Linux these are open file description locks (`F_OFD_SETLK`). They
+    /// conflict between separately opened `File` objects, even within the
+    /// same process, and they are held until the [`FileGuard`] is dropped or
+    /// the last descriptor of that `File` is closed. On Windows these are the
+    /// same as the [`Default`] locks, which are already owned by the handle.
+    /// Other platforms return an `Error` of kind `ErrorKind::Unsupported`.
+    ///
+    /// [`FileGuard`]: struct.FileGuard.html
+    /// [`Default`]: enum.Backend.html#variant.Default
+    OpenFile,
+}
+
+impl Backend {
+    /// Wait and claim the desired [`Lock`] type using a byte range of a file.
+    ///
+    /// The byte range does not need to exist in the underlying file.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    pub fn lock<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(Some(lock), offset, len, Wait::Block)
+            .acquire(file)
+    }
+
+    /// Attempt to claim the desired [`Lock`] type using a byte range of a
+    /// file.
+    ///
+    /// If the desired [`Lock`] type cannot be obtained without blocking, an
+    /// `Error` of kind `ErrorKind::WouldBlock` is returned. Otherwise if
+    /// successful, the lock is held.
+    ///
+    /// The byte range does not need to exist in the underlying file.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    pub fn try_lock<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(Some(lock), offset, len, Wait::Try)
+            .acquire(file)
+    }
+
+    /// Wait up to `timeout` to claim the desired [`Lock`] type using a byte
+    /// range of a file.
+    ///
+    /// See [`lock_timeout()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`lock_timeout()`]: fn.lock_timeout.html
+    pub fn lock_timeout<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+        offset: u64,
+        len: u64,
+        timeout: Duration,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(Some(lock), offset, len, Wait::Timeout(timeout))
+            .acquire(file)
+    }
+
+    /// Wait until `deadline` to claim the desired [`Lock`] type using a byte
+    /// range of a file.
+    ///
+    /// See [`lock_deadline()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`lock_deadline()`]: fn.lock_deadline.html
+    pub fn lock_deadline<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+        offset: u64,
+        len: u64,
+        deadline: Instant,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(Some(lock), offset, len, Wait::Deadline(deadline))
+            .acquire(file)
+    }
+
+    /// Wait and claim the desired [`Lock`] type for the whole file.
+    ///
+    /// See [`lock_file()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`lock_file()`]: fn.lock_file.html
+    pub fn lock_file<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(Some(lock), 0, None, Wait::Block).acquire(file)
+    }
+
+    /// Attempt to claim the desired [`Lock`] type for the whole file.
+    ///
+    /// See [`try_lock_file()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`try_lock_file()`]: fn.try_lock_file.html
+    pub fn try_lock_file<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(Some(lock), 0, None, Wait::Try).acquire(file)
+    }
+
+    /// Wait and claim the desired [`Lock`] types for several byte ranges of a
+    /// file.
+    ///
+    /// See [`lock_many()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`lock_many()`]: fn.lock_many.html
+    pub fn lock_many<T: Deref<Target = File> + Clone>(
+        self,
+        file: T,
+        ranges: &[(Lock, Range<u64>)],
+    ) -> io::Result<GuardSet<T>> {
+        let requests = ranges
+            .iter()
+            .map(|(lock, range)| (file.clone(), *lock, range.clone()));
+        set::acquire(self, requests, Wait::Block)
+    }
+
+    /// Attempt to claim the desired [`Lock`] types for several byte ranges
+    /// of a file.
+    ///
+    /// See [`try_lock_many()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`try_lock_many()`]: fn.try_lock_many.html
+    pub fn try_lock_many<T: Deref<Target = File> + Clone>(
+        self,
+        file: T,
+        ranges: &[(Lock, Range<u64>)],
+    ) -> io::Result<GuardSet<T>> {
+        let requests = ranges
+            .iter()
+            .map(|(lock, range)| (file.clone(), *lock, range.clone()));
+        set::acquire(self, requests, Wait::Try)
+    }
+
+    /// Wait and claim the desired [`Lock`] types for byte ranges of several
+    /// files.
+    ///
+    /// See [`lock_all()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`lock_all()`]: fn.lock_all.html
+    pub fn lock_all<T, I>(self, requests: I) -> io::Result<GuardSet<T>>
+    where
+        T: Deref<Target = File>,
+        I: IntoIterator<Item = (T, Lock, Range<u64>)>,
+    {
+        set::acquire(self, requests, Wait::Block)
+    }
+
+    /// Attempt to claim the desired [`Lock`] types for byte ranges of
+    /// several files.
+    ///
+    /// See [`try_lock_all()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`try_lock_all()`]: fn.try_lock_all.html
+    pub fn try_lock_all<T, I>(self, requests: I) -> io::Result<GuardSet<T>>
+    where
+        T: Deref<Target = File>,
+        I: IntoIterator<Item = (T, Lock, Range<u64>)>,
+    {
+        set::acquire(self, requests, Wait::Try)
+    }
+
+    /// Wait and claim either an [`Exclusive`] or a [`Shared`] lock for a
+    /// byte range of a file, whichever is available first.
+    ///
+    /// See [`lock_any()`] for details.
+    ///
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`lock_any()`]: fn.lock_any.html
+    pub fn lock_any<T: Deref<Target = File>>(
+        self,
+        file: T,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(None, offset, len, Wait::Block).acquire(file)
+    }
+
+    /// Attempt to claim either an [`Exclusive`] or a [`Shared`] lock for a
+    /// byte range of a file.
+    ///
+    /// See [`try_lock_any()`] for details.
+    ///
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`try_lock_any()`]: fn.try_lock_any.html
+    pub fn try_lock_any<T: Deref<Target = File>>(
+        self,
+        file: T,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<FileGuard<T>> {
+        self.options(None, offset, len, Wait::Try).acquire(file)
+    }
+
+    /// Wait and claim the desired [`Lock`] type using a byte range of a file,
+    /// without starving writers.
+    ///
+    /// See [`lock_fair()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`lock_fair()`]: fn.lock_fair.html
+    pub fn lock_fair<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<FileGuard<T>> {
+        fair::acquire(self, file, lock, offset, len, true)
+    }
+
+    /// Attempt to claim the desired [`Lock`] type using a byte range of a
+    /// file, without starving writers.
+    ///
+    /// See [`try_lock_fair()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`try_lock_fair()`]: fn.try_lock_fair.html
+    pub fn try_lock_fair<T: Deref<Target = File>>(
+        self,
+        file: T,
+        lock: Lock,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<FileGuard<T>> {
+        fair::acquire(self, file, lock, offset, len, false)
+    }
+
+    /// Wait and claim an upgradable [`Shared`] lock for a byte range of a
+    /// file.
+    ///
+    /// See [`lock_upgradable()`] for details.
+    ///
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`lock_upgradable()`]: fn.lock_upgradable.html
+    pub fn lock_upgradable<T: Deref<Target = File>>(
+        self,
+        file: T,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<UpgradableGuard<T>> {
+        upgradable::acquire(self, file, offset, len, true)
+    }
+
+    /// Attempt to claim an upgradable [`Shared`] lock for a byte range of a
+    /// file.
+    ///
+    /// See [`try_lock_upgradable()`] for details.
+    ///
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`try_lock_upgradable()`]: fn.try_lock_upgradable.html
+    pub fn try_lock_upgradable<T: Deref<Target = File>>(
+        self,
+        file: T,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<UpgradableGuard<T>> {
+        upgradable::acquire(self, file, offset, len, false)
+    }
+
+    /// Find a lock that would prevent claiming the desired [`Lock`] type using
+    /// a byte range of a file.
+    ///
+    /// See [`probe()`] for details.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`probe()`]: fn.probe.html
+    pub fn probe(
+        self,
+        file: &File,
+        lock: Lock,
+        offset: u64,
+        len: u64,
+    ) -> io::Result<Option<Conflict>> {
+        if len == 0 {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+        unsafe { raw_backend_probe(file, self, lock, offset, len) }
+    }
+
+    fn options(
+        self,
+        lock: Option<Lock>,
+        offset: u64,
+        len: impl Into<Option<u64>>,
+        wait: Wait,
+    ) -> LockOptions {
+        LockOptions {
+            lock,
+            offset,
+            len: len.into(),
+            wait,
+            backend: self,
+            restart: true,
+        }
+    }
+}
+
 /// Wait and claim the desired [`Lock`] type using a byte range of a file.
 ///
 /// The byte range does not need to exist in the underlying file.
@@ -166,18 +655,10 @@ pub enum Lock {
 pub fn lock<T: Deref<Target = File>>(
     file: T,
     lock: Lock,
-    offset: usize,
-    len: usize,
+    offset: u64,
+    len: u64,
 ) -> io::Result<FileGuard<T>> {
-    unsafe {
-        raw_file_lock(&file, Some(lock), offset, len, true)?;
-    }
-    Ok(FileGuard {
-        offset,
-        len,
-        file,
-        lock,
-    })
+    Backend::Default.lock(file, lock, offset, len)
 }
 
 /// Attempt to claim the desired [`Lock`] type using a byte range of a file.
@@ -192,23 +673,278 @@ pub fn lock<T: Deref<Target = File>>(
 pub fn try_lock<T: Deref<Target = File>>(
     file: T,
     lock: Lock,
-    offset: usize,
-    len: usize,
+    offset: u64,
+    len: u64,
 ) -> io::Result<FileGuard<T>> {
-    unsafe {
-        raw_file_lock(&file, Some(lock), offset, len, false)?;
-    }
-    Ok(FileGuard {
-        offset,
-        len,
-        file,
-        lock,
-    })
+    Backend::Default.try_lock(file, lock, offset, len)
 }
 
-/// First attempt to claim an [`Exclusive`] lock and then fallback to a
-/// [`Shared`] lock for a byte range of a file. This is not currently an
-/// atomic operation.
+/// Wait and claim the desired [`Lock`] type for the whole file.
+///
+/// The lock extends from the start of the file to the end of the file and
+/// beyond, so it continues to cover the file as it grows. Use
+/// [`LockOptions::range()`] to lock from an offset to the end of the file.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::Lock;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// let lock = file_guard::lock_file(&file, Lock::Exclusive)?;
+/// assert!(lock.is_unbounded());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lock`]: enum.Lock.html
+/// [`LockOptions::range()`]: struct.LockOptions.html#method.range
+pub fn lock_file<T: Deref<Target = File>>(file: T, lock: Lock) -> io::Result<FileGuard<T>> {
+    Backend::Default.lock_file(file, lock)
+}
+
+/// Attempt to claim the desired [`Lock`] type for the whole file.
+///
+/// If the desired [`Lock`] type cannot be obtained without blocking, an
+/// `Error` of kind `ErrorKind::WouldBlock` is returned. Otherwise if
+/// successful, the lock is held. As with [`lock_file()`], the lock covers the
+/// file as it grows.
+///
+/// [`Lock`]: enum.Lock.html
+/// [`lock_file()`]: fn.lock_file.html
+pub fn try_lock_file<T: Deref<Target = File>>(file: T, lock: Lock) -> io::Result<FileGuard<T>> {
+    Backend::Default.try_lock_file(file, lock)
+}
+
+/// Wait and claim the desired [`Lock`] types for several byte ranges of a
+/// file.
+///
+/// The ranges are locked in order of offset rather than the order given, so
+/// processes locking overlapping sets of ranges cannot deadlock each other.
+/// Each range must be non-empty and must not overlap another, or an `Error`
+/// of kind `ErrorKind::InvalidInput` is returned. A range ending at
+/// `u64::MAX` extends to the end of the file and beyond. If any lock fails,
+/// the locks already claimed are released before the error is returned.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::Lock;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// let locks = file_guard::lock_many(&file, &[
+///     (Lock::Exclusive, 4096..8192),
+///     (Lock::Shared, 0..64),
+/// ])?;
+/// assert_eq!(locks.get(0).unwrap().range(), 0..64);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lock`]: enum.Lock.html
+pub fn lock_many<T: Deref<Target = File> + Clone>(
+    file: T,
+    ranges: &[(Lock, Range<u64>)],
+) -> io::Result<GuardSet<T>> {
+    Backend::Default.lock_many(file, ranges)
+}
+
+/// Attempt to claim the desired [`Lock`] types for several byte ranges of a
+/// file.
+///
+/// If any of the locks cannot be obtained without blocking, every lock
+/// already claimed is released and an `Error` of kind `ErrorKind::WouldBlock`
+/// is returned. Otherwise if successful, all of the locks are held. The ranges
+/// are given as with [`lock_many()`].
+///
+/// [`Lock`]: enum.Lock.html
+/// [`lock_many()`]: fn.lock_many.html
+pub fn try_lock_many<T: Deref<Target = File> + Clone>(
+    file: T,
+    ranges: &[(Lock, Range<u64>)],
+) -> io::Result<GuardSet<T>> {
+    Backend::Default.try_lock_many(file, ranges)
+}
+
+/// Wait and claim the desired [`Lock`] types for byte ranges of several
+/// files.
+///
+/// Each request gives a file, the [`Lock`] type, and the byte range to lock
+/// as with [`lock_many()`]. The requests are claimed in a canonical order, by
+/// the device and inode of the file, or the volume and file index on Windows,
+/// and then by offset. Processes that only lock files through this order
+/// cannot deadlock each other. The same file may appear more than once, even
+/// when opened separately, but its ranges must not overlap.
+///
+/// If any lock fails, the locks already claimed are released before the
+/// error is returned. When waiting for an `fcntl` lock, the kernel may detect
+/// a deadlock with a process that takes its locks in a different order, which
+/// fails with an `Error` of kind `ErrorKind::Deadlock`. Having released every
+/// lock, the caller may back off and retry.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::Lock;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let index = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-index")?;
+/// let data = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-data")?;
+///
+/// let locks = file_guard::lock_all([
+///     (&data, Lock::Exclusive, 128..256),
+///     (&index, Lock::Exclusive, 0..8),
+/// ])?;
+/// assert_eq!(locks.len(), 2);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lock`]: enum.Lock.html
+/// [`lock_many()`]: fn.lock_many.html
+pub fn lock_all<T, I>(requests: I) -> io::Result<GuardSet<T>>
+where
+    T: Deref<Target = File>,
+    I: IntoIterator<Item = (T, Lock, Range<u64>)>,
+{
+    Backend::Default.lock_all(requests)
+}
+
+/// Attempt to claim the desired [`Lock`] types for byte ranges of several
+/// files.
+///
+/// If any of the locks cannot be obtained without blocking, every lock
+/// already claimed is released and an `Error` of kind `ErrorKind::WouldBlock`
+/// is returned. Otherwise if successful, all of the locks are held. The
+/// requests are given as with [`lock_all()`].
+///
+/// [`Lock`]: enum.Lock.html
+/// [`lock_all()`]: fn.lock_all.html
+pub fn try_lock_all<T, I>(requests: I) -> io::Result<GuardSet<T>>
+where
+    T: Deref<Target = File>,
+    I: IntoIterator<Item = (T, Lock, Range<u64>)>,
+{
+    Backend::Default.try_lock_all(requests)
+}
+
+/// Wait up to `timeout` to claim the desired [`Lock`] type using a byte range
+/// of a file.
+///
+/// If the desired [`Lock`] type cannot be obtained before the timeout
+/// elapses, an `Error` of kind `ErrorKind::TimedOut` is returned. Otherwise if
+/// successful, the lock is held.
+///
+/// The lock is polled without blocking, so a lock is never left held when
+/// the timeout is reached. A timeout too large to ever elapse, such as
+/// `Duration::MAX`, polls until the lock is available. The byte range does
+/// not need to exist in the underlying file.
+///
+/// [`Lock`]: enum.Lock.html
+pub fn lock_timeout<T: Deref<Target = File>>(
+    file: T,
+    lock: Lock,
+    offset: u64,
+    len: u64,
+    timeout: Duration,
+) -> io::Result<FileGuard<T>> {
+    Backend::Default.lock_timeout(file, lock, offset, len, timeout)
+}
+
+/// Wait until `deadline` to claim the desired [`Lock`] type using a byte range
+/// of a file.
+///
+/// If the desired [`Lock`] type cannot be obtained before the deadline
+/// passes, an `Error` of kind `ErrorKind::TimedOut` is returned. Otherwise if
+/// successful, the lock is held. A final attempt is always made once the
+/// deadline is reached, even if the deadline has already passed.
+///
+/// The lock is polled without blocking, so a lock is never left held when
+/// the deadline passes. The byte range does not need to exist in the
+/// underlying file.
+///
+/// [`Lock`]: enum.Lock.html
+pub fn lock_deadline<T: Deref<Target = File>>(
+    file: T,
+    lock: Lock,
+    offset: u64,
+    len: u64,
+    deadline: Instant,
+) -> io::Result<FileGuard<T>> {
+    Backend::Default.lock_deadline(file, lock, offset, len, deadline)
+}
+
+/// Find a lock that would prevent claiming the desired [`Lock`] type using a
+/// byte range of a file.
+///
+/// If the lock could be claimed, `None` is returned. Otherwise, one of the
+/// conflicting locks is described by a [`Conflict`]. No lock is claimed, so
+/// the result is only a snapshot and may be stale by the time it is used.
+///
+/// On Unix systems, the kernel does not report locks held by the calling
+/// process, but the conflicting guards of the process are reported from the
+/// registry of the [`Default`] backend with the pid of the process. On
+/// Windows, existing locks cannot be queried, so the lock is briefly claimed
+/// and released instead, and the reported range is the requested range.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::Lock;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// if let Some(conflict) = file_guard::probe(&file, Lock::Exclusive, 0, 1)? {
+///     println!("blocked by {}", conflict);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lock`]: enum.Lock.html
+/// [`Conflict`]: struct.Conflict.html
+/// [`Default`]: enum.Backend.html#variant.Default
+pub fn probe(file: &File, lock: Lock, offset: u64, len: u64) -> io::Result<Option<Conflict>> {
+    Backend::Default.probe(file, lock, offset, len)
+}
+
+/// Wait and claim either an [`Exclusive`] or a [`Shared`] lock for a byte
+/// range of a file, whichever is available first.
+///
+/// An [`Exclusive`] lock is attempted first, falling back to a [`Shared`]
+/// lock. Neither attempt blocks, so the caller is never left waiting for a
+/// [`Shared`] lock while an [`Exclusive`] lock has become available, or the
+/// other way around. If neither is available, both are attempted again after
+/// an exponentially increasing delay until one is obtained.
 ///
 /// When successful, the [`FileGuard`] may be inspected for the lock type
 /// obtained using [`.lock_type()`], [`.is_shared()`], or [`.is_exclusive()`].
@@ -223,45 +959,208 @@ pub fn try_lock<T: Deref<Target = File>>(
 /// [`.is_exclusive()`]: struct.FileGuard.html#method.is_exclusive
 pub fn lock_any<T: Deref<Target = File>>(
     file: T,
-    offset: usize,
-    len: usize,
+    offset: u64,
+    len: u64,
 ) -> io::Result<FileGuard<T>> {
-    let lock = match unsafe { raw_file_lock(&file, Some(Lock::Exclusive), offset, len, false) } {
-        Ok(_) => Lock::Exclusive,
-        Err(e) => {
-            if e.kind() == ErrorKind::WouldBlock {
-                unsafe {
-                    raw_file_lock(&file, Some(Lock::Shared), offset, len, true)?;
-                }
-                Lock::Shared
-            } else {
-                return Err(e);
-            }
-        }
-    };
-    Ok(FileGuard {
-        offset,
-        len,
-        file,
-        lock,
-    })
+    Backend::Default.lock_any(file, offset, len)
+}
+
+/// Attempt to claim either an [`Exclusive`] or a [`Shared`] lock for a byte
+/// range of a file.
+///
+/// An [`Exclusive`] lock is attempted first, falling back to a [`Shared`]
+/// lock, without blocking for either. If neither lock type is available, an
+/// `Error` of kind `ErrorKind::WouldBlock` is returned. Otherwise if
+/// successful, the [`FileGuard`] may be inspected for the lock type obtained.
+///
+/// The byte range does not need to exist in the underlying file.
+///
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+/// [`Shared`]: enum.Lock.html#variant.Shared
+/// [`FileGuard`]: struct.FileGuard.html
+pub fn try_lock_any<T: Deref<Target = File>>(
+    file: T,
+    offset: u64,
+    len: u64,
+) -> io::Result<FileGuard<T>> {
+    Backend::Default.try_lock_any(file, offset, len)
+}
+
+/// Wait and claim the desired [`Lock`] type using a byte range of a file,
+/// without starving writers.
+///
+/// A steady stream of [`Shared`] locks may otherwise keep a waiting
+/// [`Exclusive`] lock from ever being claimed. Fair locks use the pending
+/// byte at `offset + len + 1`, which follows the intent byte of an
+/// [`UpgradableGuard`]. A waiting [`Exclusive`] lock holds the pending byte
+/// [`Exclusive`] until it is claimed, and a [`Shared`] lock first waits for a
+/// [`Shared`] lock of the pending byte, so new readers queue behind a waiting
+/// writer. Before the pending byte, an [`Exclusive`] lock claims the intent
+/// byte, so it waits for an upgrading [`UpgradableGuard`] rather than
+/// deadlocking with it. These bytes are released before returning, and the
+/// guard only holds the byte range. Every process must use fair locks for the byte range
+/// for the protocol to hold.
+///
+/// If `len` is zero, or the pending byte would be beyond the largest offset,
+/// an `Error` of kind `ErrorKind::InvalidInput` is returned.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::Lock;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// let lock = file_guard::lock_fair(&file, Lock::Exclusive, 0, 64)?;
+/// assert_eq!(lock.range(), 0..64);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Lock`]: enum.Lock.html
+/// [`Shared`]: enum.Lock.html#variant.Shared
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+/// [`UpgradableGuard`]: struct.UpgradableGuard.html
+pub fn lock_fair<T: Deref<Target = File>>(
+    file: T,
+    lock: Lock,
+    offset: u64,
+    len: u64,
+) -> io::Result<FileGuard<T>> {
+    Backend::Default.lock_fair(file, lock, offset, len)
+}
+
+/// Attempt to claim the desired [`Lock`] type using a byte range of a file,
+/// without starving writers.
+///
+/// If either the pending byte or the byte range cannot be locked without
+/// blocking, an `Error` of kind `ErrorKind::WouldBlock` is returned.
+/// Otherwise if successful, the lock is held as with [`lock_fair()`].
+///
+/// [`Lock`]: enum.Lock.html
+/// [`lock_fair()`]: fn.lock_fair.html
+pub fn try_lock_fair<T: Deref<Target = File>>(
+    file: T,
+    lock: Lock,
+    offset: u64,
+    len: u64,
+) -> io::Result<FileGuard<T>> {
+    Backend::Default.try_lock_fair(file, lock, offset, len)
+}
+
+/// Wait and claim an upgradable [`Shared`] lock for a byte range of a file.
+///
+/// An [`Exclusive`] lock is first claimed on the intent byte at
+/// `offset + len`, waiting for any other [`UpgradableGuard`] of the byte
+/// range to be dropped, and then a [`Shared`] lock is claimed on the byte
+/// range as with [`lock_fair()`]. The guard may later be upgraded without
+/// risk of deadlock. If `len` is zero, or the intent byte or the pending byte
+/// that follows it would be beyond the largest offset, an `Error` of kind
+/// `ErrorKind::InvalidInput` is returned.
+///
+/// [`Shared`]: enum.Lock.html#variant.Shared
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+/// [`UpgradableGuard`]: struct.UpgradableGuard.html
+/// [`lock_fair()`]: fn.lock_fair.html
+pub fn lock_upgradable<T: Deref<Target = File>>(
+    file: T,
+    offset: u64,
+    len: u64,
+) -> io::Result<UpgradableGuard<T>> {
+    Backend::Default.lock_upgradable(file, offset, len)
+}
+
+/// Attempt to claim an upgradable [`Shared`] lock for a byte range of a file.
+///
+/// If the intent byte or the byte range cannot be locked without blocking,
+/// an `Error` of kind `ErrorKind::WouldBlock` is returned. Otherwise if
+/// successful, the lock is held as with [`lock_upgradable()`].
+///
+/// [`Shared`]: enum.Lock.html#variant.Shared
+/// [`lock_upgradable()`]: fn.lock_upgradable.html
+pub fn try_lock_upgradable<T: Deref<Target = File>>(
+    file: T,
+    offset: u64,
+    len: u64,
+) -> io::Result<UpgradableGuard<T>> {
+    Backend::Default.try_lock_upgradable(file, offset, len)
+}
+
+/// Checks if an error reports a deadlock between waiting locks.
+///
+/// On Unix systems, waiting for an `fcntl` lock fails with `EDEADLK` when the
+/// kernel finds that the lock is held by a process that is itself waiting on
+/// a lock held by the caller. Guards of the [`Default`] backend fail the same
+/// way when upgrading while another guard of the process is already upgrading
+/// an overlapping range, and, once enabled with `set_deadlock_detection`,
+/// when waiting on a guard claimed by the same thread.
+/// These errors have the kind `ErrorKind::Deadlock`. No lock is claimed by
+/// the failed call, so the caller may release its other locks, back off, and
+/// retry. Windows does not detect deadlocks, so this is never true for its
+/// locks.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::Lock;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// match file_guard::lock(&file, Lock::Exclusive, 0, 1) {
+///     Err(e) if file_guard::is_deadlock(&e) => println!("deadlock, retrying"),
+///     result => drop(result?),
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Default`]: enum.Backend.html#variant.Default
+pub fn is_deadlock(err: &io::Error) -> bool {
+    err.kind() == io::ErrorKind::Deadlock
+}
+
+/// Allocates a unique id for a lock, which tells it apart from other locks of
+/// the process over the same byte range.
+pub(crate) fn next_lock_id() -> u64 {
+    static NEXT: AtomicU64 = AtomicU64::new(1);
+    NEXT.fetch_add(1, Ordering::Relaxed)
 }
 
 /// An RAII implementation of a "scoped lock" of a file. When this structure
 /// is dropped (falls out of scope), the lock will be unlocked.
 ///
+/// Errors that occur while unlocking a dropped guard are passed to the hook
+/// registered with [`set_unlock_error_hook()`], and are otherwise ignored.
+/// Use [`.unlock()`] to release the lock and handle any error directly.
+///
 /// This structure is created by the [`lock()`], [`try_lock()`], and
 /// [`lock_any()`] functions.
 ///
 /// [`lock()`]: fn.lock.html
 /// [`try_lock()`]: fn.try_lock.html
 /// [`lock_any()`]: fn.lock_any.html
+/// [`set_unlock_error_hook()`]: fn.set_unlock_error_hook.html
+/// [`.unlock()`]: struct.FileGuard.html#method.unlock
 #[must_use = "if unused the file lock will immediately unlock"]
 pub struct FileGuard<T: Deref<Target = File>> {
-    offset: usize,
-    len: usize,
+    offset: u64,
+    len: Option<u64>,
     file: T,
     lock: Lock,
+    backend: Backend,
+    id: u64,
 }
 
 impl<T> fmt::Debug for FileGuard<T>
@@ -269,11 +1168,10 @@ where
     T: Deref<Target = File>,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "FileGuard::{:?}({}, {})",
-            self.lock, self.offset, self.len
-        )
+        match self.len {
+            Some(len) => write!(f, "FileGuard::{:?}({}, {})", self.lock, self.offset, len),
+            None => write!(f, "FileGuard::{:?}({}, ..)", self.lock, self.offset),
+        }
     }
 }
 
@@ -307,28 +1205,65 @@ where
         self.lock == Lock::Exclusive
     }
 
+    /// Gets the [`Backend`] used to hold the lock.
+    ///
+    /// [`Backend`]: enum.Backend.html
+    #[inline]
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
     /// Gets the byte range of the held lock.
+    ///
+    /// The range of an unbounded lock ends at `u64::MAX`.
     #[inline]
-    pub fn range(&self) -> Range<usize> {
-        self.offset..(self.offset + self.len)
+    pub fn range(&self) -> Range<u64> {
+        self.offset..self.end()
     }
 
     /// Gets the byte offset of the held lock.
     #[inline]
-    pub fn offset(&self) -> usize {
+    pub fn offset(&self) -> u64 {
         self.offset
     }
 
     /// Gets the byte length of the held lock.
+    ///
+    /// The length of an unbounded lock extends to `u64::MAX`.
     #[inline]
-    pub fn len(&self) -> usize {
-        self.len
+    pub fn len(&self) -> u64 {
+        self.end() - self.offset
     }
 
     /// Tests if the byte range of the lock has a length of zero.
     #[inline]
     pub fn is_empty(&self) -> bool {
-        self.len == 0
+        self.len() == 0
+    }
+
+    /// Tests if the lock extends to the end of the file and beyond.
+    ///
+    /// Such a lock is obtained with [`lock_file()`] or an open-ended range
+    /// given to [`LockOptions::range()`].
+    ///
+    /// [`lock_file()`]: fn.lock_file.html
+    /// [`LockOptions::range()`]: struct.LockOptions.html#method.range
+    #[inline]
+    pub fn is_unbounded(&self) -> bool {
+        self.len.is_none()
+    }
+
+    fn end(&self) -> u64 {
+        match self.len {
+            Some(len) => self.offset + len,
+            None => u64::MAX,
+        }
+    }
+
+    /// Gets the length passed to the raw lock functions, where zero extends
+    /// the lock to the end of the file and beyond.
+    fn raw_len(&self) -> u64 {
+        self.len.unwrap_or(0)
     }
 
     /// Safely exchanges an [`Exclusive`] [`Lock`] for a [`Shared`] one.
@@ -345,12 +1280,352 @@ where
     pub fn downgrade(&mut self) -> io::Result<()> {
         if self.is_exclusive() {
             unsafe {
-                raw_file_downgrade(&self.file, self.offset, self.len)?;
+                let (id, off, len) = (self.id, self.offset, self.raw_len());
+                raw_backend_downgrade(&self.file, self.backend, id, off, len)?;
             }
             self.lock = Lock::Shared;
         }
         Ok(())
     }
+
+    /// Shrinks the held lock to a byte range within its current range,
+    /// unlocking the bytes outside of it.
+    ///
+    /// A bound that is not given keeps the current start or end of the lock,
+    /// so `16..` releases the bytes before offset 16. The bytes that remain
+    /// are never unlocked during the operation. If `range` is empty or not
+    /// within the current range, an `Error` of kind `ErrorKind::InvalidInput`
+    /// is returned.
+    ///
+    /// On Windows, a lock can only be released as a whole, so shrinking fails
+    /// with an `Error` of kind `ErrorKind::Unsupported`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use file_guard::Lock;
+    /// use std::fs::OpenOptions;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let file = OpenOptions::new()
+    ///     .read(true)
+    ///     .write(true)
+    ///     .create(true)
+    ///     .open("example-lock")?;
+    ///
+    /// let mut lock = file_guard::lock(&file, Lock::Exclusive, 64, 64)?;
+    /// # #[cfg(unix)]
+    /// # {
+    /// lock.shrink_to(96..112)?;
+    /// assert_eq!(lock.range(), 96..112);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn shrink_to<R: RangeBounds<u64>>(&mut self, range: R) -> io::Result<()> {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.saturating_add(1),
+            Bound::Unbounded => self.offset,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.saturating_add(1),
+            Bound::Excluded(&n) => n,
+            Bound::Unbounded => self.end(),
+        };
+        if start < self.offset || end > self.end() || start >= end {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        unsafe {
+            raw_backend_shrink(&self.file, self.backend, self.id, self.range(), start..end)?;
+        }
+        self.len = match self.len {
+            None if end == u64::MAX => None,
+            _ => Some(end - start),
+        };
+        self.offset = start;
+        Ok(())
+    }
+
+    /// Releases the first `n` bytes of the held lock.
+    ///
+    /// This is the same as calling [`.shrink_to()`] with the range starting
+    /// `n` bytes past the current offset. Releasing every byte of the lock
+    /// fails with an `Error` of kind `ErrorKind::InvalidInput`, as the lock
+    /// is released as a whole by dropping it.
+    ///
+    /// [`.shrink_to()`]: struct.FileGuard.html#method.shrink_to
+    pub fn release_prefix(&mut self, n: u64) -> io::Result<()> {
+        match self.offset.checked_add(n) {
+            Some(start) => self.shrink_to(start..),
+            None => Err(io::ErrorKind::InvalidInput.into()),
+        }
+    }
+
+    /// Splits the held lock into two guards at `mid` bytes past its offset.
+    ///
+    /// The first guard holds the bytes before `mid` and the second holds the
+    /// rest, each of the same [`Lock`] type, so each may be released
+    /// independently. No bytes are unlocked during the split. The underlying
+    /// file is cloned, so this is available for guards of shared handles such
+    /// as `&File` or `Rc<File>`.
+    ///
+    /// On Windows, a lock can only be released as a whole, so splitting fails
+    /// with an `Error` of kind `ErrorKind::Unsupported`. If `mid` is zero or
+    /// not less than the length of the lock, the split fails with an `Error`
+    /// of kind `ErrorKind::InvalidInput`. If the split fails, the
+    /// [`SplitError`] holds both the error and this guard, which still holds
+    /// its whole lock.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`SplitError`]: struct.SplitError.html
+    pub fn split_at(self, mid: u64) -> Result<(FileGuard<T>, FileGuard<T>), SplitError<T>>
+    where
+        T: Clone,
+    {
+        if mid == 0 || mid >= self.len() {
+            return Err(SplitError::new(self, io::ErrorKind::InvalidInput.into()));
+        }
+
+        let at = self.offset + mid;
+        let id = next_lock_id();
+        if let Err(e) = unsafe { raw_backend_split(&self.file, self.backend, self.id, at, id) } {
+            return Err(SplitError::new(self, e));
+        }
+
+        let this = ManuallyDrop::new(self);
+        let file = unsafe { ptr::read(&this.file) };
+        let head = FileGuard {
+            offset: this.offset,
+            len: Some(mid),
+            file: file.clone(),
+            lock: this.lock,
+            backend: this.backend,
+            id: this.id,
+        };
+        let tail = FileGuard {
+            offset: at,
+            len: this.len.map(|len| len - mid),
+            file,
+            lock: this.lock,
+            backend: this.backend,
+            id,
+        };
+        Ok((head, tail))
+    }
+
+    /// Waits for and extends the held lock to a byte range containing its
+    /// current range.
+    ///
+    /// A bound that is not given extends the lock to the start of the file or
+    /// to the end of the file and beyond, so `..` covers the whole file. The
+    /// new range is claimed with the current [`Lock`] type in a single
+    /// operation, so the bytes already held are never released, and if the
+    /// extension fails the lock is left unchanged. If `range` does not contain
+    /// the current range, an `Error` of kind `ErrorKind::InvalidInput` is
+    /// returned.
+    ///
+    /// On Windows, a lock can only be released as a whole, so extending fails
+    /// with an `Error` of kind `ErrorKind::Unsupported`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use file_guard::Lock;
+    /// use std::fs::OpenOptions;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let file = OpenOptions::new()
+    ///     .read(true)
+    ///     .write(true)
+    ///     .create(true)
+    ///     .open("example-lock")?;
+    ///
+    /// let mut lock = file_guard::lock(&file, Lock::Exclusive, 128, 16)?;
+    /// # #[cfg(unix)]
+    /// # {
+    /// lock.extend_to(128..160)?;
+    /// assert_eq!(lock.range(), 128..160);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lock`]: enum.Lock.html
+    pub fn extend_to<R: RangeBounds<u64>>(&mut self, range: R) -> io::Result<()> {
+        self.extend(range, true)
+    }
+
+    /// Attempts to extend the held lock to a byte range containing its
+    /// current range.
+    ///
+    /// If the new bytes cannot be claimed without blocking, an `Error` of kind
+    /// `ErrorKind::WouldBlock` is returned and the lock is left unchanged. See
+    /// [`.extend_to()`] for details.
+    ///
+    /// [`.extend_to()`]: struct.FileGuard.html#method.extend_to
+    pub fn try_extend_to<R: RangeBounds<u64>>(&mut self, range: R) -> io::Result<()> {
+        self.extend(range, false)
+    }
+
+    fn extend<R: RangeBounds<u64>>(&mut self, range: R, wait: bool) -> io::Result<()> {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => Some(n),
+            Bound::Excluded(&n) => n.checked_add(1),
+            Bound::Unbounded => Some(0),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1).map(Some),
+            Bound::Excluded(&n) => Some(Some(n)),
+            Bound::Unbounded => Some(None),
+        };
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Err(io::ErrorKind::InvalidInput.into()),
+        };
+        if start > self.offset || end.is_some_and(|end| end < self.end()) {
+            return Err(io::ErrorKind::InvalidInput.into());
+        }
+
+        let to = start..end.unwrap_or(u64::MAX);
+        // A range ending at `u64::MAX` is locked to the end of the file.
+        let len = match to.end {
+            u64::MAX => None,
+            end => Some(end - start),
+        };
+        if to != self.range() {
+            unsafe {
+                let (id, lock, from) = (self.id, self.lock, self.range());
+                raw_backend_extend(&self.file, self.backend, id, lock, from, to, wait)?;
+            }
+        }
+        self.len = len;
+        self.offset = start;
+        Ok(())
+    }
+
+    /// Merges two guards of adjacent byte ranges into a single guard.
+    ///
+    /// The guards must hold the same [`Lock`] type of the same file, using
+    /// the same [`Backend`], and the range of one must end where the other
+    /// begins. Otherwise, an `Error` of kind `ErrorKind::InvalidInput` is
+    /// returned. No bytes are unlocked during the merge.
+    ///
+    /// On Windows, a lock can only be released as a whole, so merging fails
+    /// with an `Error` of kind `ErrorKind::Unsupported`. If the merge fails,
+    /// the [`MergeError`] holds both the error and the two guards, which
+    /// still hold their locks.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use file_guard::{FileGuard, Lock};
+    /// use std::fs::OpenOptions;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let file = OpenOptions::new()
+    ///     .read(true)
+    ///     .write(true)
+    ///     .create(true)
+    ///     .open("example-lock")?;
+    ///
+    /// let a = file_guard::lock(&file, Lock::Shared, 160, 8)?;
+    /// let b = file_guard::lock(&file, Lock::Shared, 168, 8)?;
+    /// # #[cfg(unix)]
+    /// # {
+    /// let lock = FileGuard::merge(a, b)?;
+    /// assert_eq!(lock.range(), 160..176);
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`Backend`]: enum.Backend.html
+    /// [`MergeError`]: struct.MergeError.html
+    pub fn merge(a: FileGuard<T>, b: FileGuard<T>) -> Result<FileGuard<T>, MergeError<T>> {
+        let (head, tail) = if a.offset <= b.offset {
+            (&a, &b)
+        } else {
+            (&b, &a)
+        };
+        if !ptr::eq(&*head.file, &*tail.file)
+            || head.lock != tail.lock
+            || head.backend != tail.backend
+            || head.len.map(|len| head.offset + len) != Some(tail.offset)
+        {
+            return Err(MergeError::new(a, b, io::ErrorKind::InvalidInput.into()));
+        }
+
+        let (id, other) = (head.id, tail.id);
+        if let Err(e) = unsafe { raw_backend_merge(&a.file, a.backend, id, other) } {
+            return Err(MergeError::new(a, b, e));
+        }
+
+        let (mut head, tail) = if a.offset <= b.offset { (a, b) } else { (b, a) };
+        head.len = tail.len.map(|len| tail.offset + len - head.offset);
+
+        let tail = ManuallyDrop::new(tail);
+        drop(unsafe { ptr::read(&tail.file) });
+        Ok(head)
+    }
+
+    /// Releases the lock, returning the underlying file.
+    ///
+    /// Unlike dropping the guard, this reports any error that occurs while
+    /// unlocking. On failure, the [`UnlockError`] holds both the error and
+    /// this guard, so the unlock may be retried.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use file_guard::Lock;
+    /// use std::fs::OpenOptions;
+    ///
+    /// # fn main() -> std::io::Result<()> {
+    /// let file = OpenOptions::new()
+    ///     .read(true)
+    ///     .write(true)
+    ///     .create(true)
+    ///     .open("example-lock")?;
+    ///
+    /// let lock = file_guard::lock(&file, Lock::Exclusive, 0, 1)?;
+    /// let file = lock.unlock()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`UnlockError`]: struct.UnlockError.html
+    pub fn unlock(self) -> Result<T, UnlockError<T>> {
+        match unsafe { self.raw_unlock() } {
+            Ok(()) => {
+                let this = ManuallyDrop::new(self);
+                Ok(unsafe { ptr::read(&this.file) })
+            }
+            Err(e) => Err(UnlockError::new(self, e)),
+        }
+    }
+
+    /// Forgets the guard without unlocking, returning the id of its lock so
+    /// the lock may be handed over to a new guard.
+    pub(crate) fn into_id(self) -> u64 {
+        let mut this = ManuallyDrop::new(self);
+        unsafe { ptr::drop_in_place(&mut this.file) };
+        this.id
+    }
+
+    unsafe fn raw_unlock(&self) -> io::Result<()> {
+        raw_backend_lock(
+            &self.file,
+            self.backend,
+            self.id,
+            None,
+            self.offset,
+            self.raw_len(),
+            false,
+        )
+    }
 }
 
 impl<T> Deref for FileGuard<T>
@@ -379,6 +1654,8 @@ where
 {
     #[inline]
     fn drop(&mut self) {
-        let _ = unsafe { raw_file_lock(&self.file, None, self.offset, self.len, false) };
+        if let Err(e) = unsafe { self.raw_unlock() } {
+            unlock::report(&e, self.lock, self.range());
+        }
     }
 }
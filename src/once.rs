@@ -0,0 +1,142 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::Deref;
+
+use crate::os::{raw_read_at, raw_write_at};
+use crate::{Backend, FileGuard, Lock, Wait};
+
+/// The marker written to the header once the file is initialized.
+const MARKER: [u8; 8] = *b"fgonce\0\x01";
+
+/// Initializes a file exactly once between processes.
+///
+/// The first [`HEADER_LEN`] bytes of the file are a header holding an
+/// initialized marker, and the data of the file follows the header. Each
+/// caller of [`get_or_init()`] claims an [`Exclusive`] lock of the header to
+/// check the marker. The first caller to find the marker missing also claims
+/// an [`Exclusive`] lock of the data, calls the init closure, and only then
+/// writes the marker. Other callers wait on the header until the marker is
+/// written, so the init closure is called by a single process.
+///
+/// If the init closure fails, or its process exits before the marker is
+/// written, the locks are released without the marker and the next caller
+/// calls its own init closure. The closure should therefore not rely on the
+/// data being empty, for example by truncating or overwriting it.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::OnceFile;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-once")?;
+///
+/// let lock = OnceFile::new(&file).get_or_init(|file| {
+///     // lay out the shared data following the header
+///     file.set_len(4096)
+/// })?;
+/// assert!(lock.is_shared());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`HEADER_LEN`]: #associatedconstant.HEADER_LEN
+/// [`get_or_init()`]: #method.get_or_init
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+pub struct OnceFile<T: Deref<Target = File>> {
+    file: T,
+    backend: Backend,
+}
+
+impl<T> OnceFile<T>
+where
+    T: Deref<Target = File>,
+{
+    /// The length of the header at the start of the file.
+    pub const HEADER_LEN: u64 = MARKER.len() as u64;
+
+    /// Creates a new `OnceFile` for a file.
+    ///
+    /// The file must be opened for both reading and writing.
+    pub fn new(file: T) -> Self {
+        Self::with_backend(Backend::Default, file)
+    }
+
+    /// Creates a new `OnceFile` using the locks of a [`Backend`].
+    ///
+    /// [`Backend`]: enum.Backend.html
+    pub fn with_backend(backend: Backend, file: T) -> Self {
+        OnceFile { file, backend }
+    }
+
+    /// Waits for the file to be initialized, calling `init` if no process has
+    /// initialized it yet.
+    ///
+    /// The result is a [`Shared`] [`FileGuard`] of the data following the
+    /// header, which extends to the end of the file and beyond. The init
+    /// closure is given the file while an [`Exclusive`] lock of both the
+    /// header and the data is held, and must not write to the header. If it
+    /// fails, its error is returned and the file is left uninitialized.
+    ///
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    /// [`FileGuard`]: struct.FileGuard.html
+    pub fn get_or_init<F>(self, init: F) -> io::Result<FileGuard<T>>
+    where
+        F: FnOnce(&File) -> io::Result<()>,
+    {
+        let OnceFile { file, backend } = self;
+        let header = backend.lock(&*file, Lock::Exclusive, 0, Self::HEADER_LEN)?;
+
+        let mut buf = [0; MARKER.len()];
+        raw_read_at(&file, &mut buf, 0)?;
+        let lock = if buf == MARKER {
+            Lock::Shared
+        } else {
+            Lock::Exclusive
+        };
+
+        let options = backend.options(Some(lock), Self::HEADER_LEN, None, Wait::Block);
+        let mut data = options.acquire(&*file)?;
+        if lock == Lock::Exclusive {
+            init(&file)?;
+            // The data must be durable before the marker claims it is.
+            file.sync_data()?;
+            raw_write_at(&file, &MARKER, 0)?;
+            file.sync_data()?;
+            data.downgrade()?;
+        }
+
+        // The lock is handed over to a guard that owns the file.
+        let id = data.into_id();
+        drop(header);
+        Ok(options.guard(file, Lock::Shared, id))
+    }
+
+    /// Gets a reference to the underlying file.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.file
+    }
+
+    /// Consumes the `OnceFile`, returning the underlying file.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.file
+    }
+}
+
+impl<T> fmt::Debug for OnceFile<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("OnceFile")
+    }
+}
@@ -0,0 +1,312 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::{Bound, Deref, RangeBounds};
+use std::time::{Duration, Instant};
+
+use crate::backoff::Backoff;
+use crate::os::{raw_backend_lock, raw_backend_lock_once};
+use crate::{next_lock_id, Backend, FileGuard, Lock};
+
+/// The strategy used to wait for a lock that is held elsewhere.
+///
+/// This is used with [`LockOptions::wait()`].
+///
+/// [`LockOptions::wait()`]: struct.LockOptions.html#method.wait
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Wait {
+    /// Block until the lock is available.
+    Block,
+    /// Fail with an `Error` of kind `ErrorKind::WouldBlock` if the lock is not
+    /// immediately available.
+    Try,
+    /// Poll for the lock until it is available or the duration elapses,
+    /// failing with an `Error` of kind `ErrorKind::TimedOut`. A duration too
+    /// large to elapse polls without a deadline.
+    Timeout(Duration),
+    /// Poll for the lock until it is available or the instant passes,
+    /// failing with an `Error` of kind `ErrorKind::TimedOut`.
+    Deadline(Instant),
+    /// Poll for the lock until it is available, sleeping between attempts
+    /// for an exponentially increasing delay between `min` and `max`. Delays
+    /// shorter than a millisecond are raised to one millisecond.
+    Backoff {
+        /// The delay after the first failed attempt.
+        min: Duration,
+        /// The longest delay between attempts.
+        max: Duration,
+    },
+}
+
+impl Wait {
+    /// Gets the backoff and deadline used to poll with this strategy, or
+    /// `None` if only a single attempt should be made.
+    pub(crate) fn polling(self) -> Option<(Backoff, Option<Instant>)> {
+        match self {
+            Wait::Try => None,
+            Wait::Block => Some((Backoff::new(), None)),
+            // A timeout beyond any representable instant never elapses.
+            Wait::Timeout(timeout) => Some((Backoff::new(), Instant::now().checked_add(timeout))),
+            Wait::Deadline(deadline) => Some((Backoff::new(), Some(deadline))),
+            Wait::Backoff { min, max } => Some((Backoff::with_delays(min, max), None)),
+        }
+    }
+}
+
+/// Options and flags which can be used to configure how a lock is acquired.
+///
+/// This builder exposes the ability to configure how a [`FileGuard`] is
+/// obtained: the [`Lock`] type, the byte range, how to wait for the lock, the
+/// [`Backend`] used, and whether interrupted calls are restarted. The
+/// [`lock()`], [`try_lock()`], and [`lock_any()`] functions are shortcuts for
+/// common configurations.
+///
+/// Generally speaking, when using `LockOptions`, you'll first call
+/// [`new()`], then chain calls to methods to set each option, then call
+/// [`acquire()`], passing the file you want to lock.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::{Lock, LockOptions, Wait};
+/// use std::fs::OpenOptions;
+/// use std::time::Duration;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// let lock = LockOptions::new()
+///     .lock(Lock::Shared)
+///     .range(0..16)
+///     .wait(Wait::Timeout(Duration::from_secs(1)))
+///     .acquire(&file)?;
+/// assert!(lock.is_shared());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FileGuard`]: struct.FileGuard.html
+/// [`Lock`]: enum.Lock.html
+/// [`Backend`]: enum.Backend.html
+/// [`lock()`]: fn.lock.html
+/// [`try_lock()`]: fn.try_lock.html
+/// [`lock_any()`]: fn.lock_any.html
+/// [`new()`]: struct.LockOptions.html#method.new
+/// [`acquire()`]: struct.LockOptions.html#method.acquire
+#[derive(Clone, Debug)]
+pub struct LockOptions {
+    pub(crate) lock: Option<Lock>,
+    pub(crate) offset: u64,
+    pub(crate) len: Option<u64>,
+    pub(crate) wait: Wait,
+    pub(crate) backend: Backend,
+    pub(crate) restart: bool,
+}
+
+impl Default for LockOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LockOptions {
+    /// Creates a blank new set of options ready for configuration.
+    ///
+    /// By default, an [`Exclusive`] lock is acquired for the first byte of the
+    /// file, blocking until it is available, using the [`Default`] backend,
+    /// and restarting calls interrupted by a signal.
+    ///
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    /// [`Default`]: enum.Backend.html#variant.Default
+    pub fn new() -> Self {
+        LockOptions {
+            lock: Some(Lock::Exclusive),
+            offset: 0,
+            len: Some(1),
+            wait: Wait::Block,
+            backend: Backend::Default,
+            restart: true,
+        }
+    }
+
+    /// Sets the [`Lock`] type to acquire.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    pub fn lock(&mut self, lock: Lock) -> &mut Self {
+        self.lock = Some(lock);
+        self
+    }
+
+    /// Acquires whichever [`Lock`] type is available.
+    ///
+    /// An [`Exclusive`] lock is attempted first, falling back to a [`Shared`]
+    /// lock, as with [`lock_any()`]. Neither attempt blocks, so when waiting,
+    /// both lock types are polled until one is obtained, even with
+    /// [`Wait::Block`]. The [`FileGuard`] may be inspected for the lock type
+    /// obtained.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`lock_any()`]: fn.lock_any.html
+    /// [`Wait::Block`]: enum.Wait.html#variant.Block
+    /// [`FileGuard`]: struct.FileGuard.html
+    pub fn any(&mut self) -> &mut Self {
+        self.lock = None;
+        self
+    }
+
+    /// Sets the byte range to lock.
+    ///
+    /// The byte range does not need to exist in the underlying file. A range
+    /// without an end, such as `16..` or `..`, extends the lock to the end of
+    /// the file and beyond, so it covers the file as it grows. An empty range
+    /// fails to acquire with an `Error` of kind `ErrorKind::InvalidInput`.
+    pub fn range<R: RangeBounds<u64>>(&mut self, range: R) -> &mut Self {
+        let start = match range.start_bound() {
+            Bound::Included(&n) => n,
+            Bound::Excluded(&n) => n.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&n) => n.checked_add(1),
+            Bound::Excluded(&n) => Some(n),
+            Bound::Unbounded => None,
+        };
+        self.offset = start;
+        self.len = end.map(|end| end.saturating_sub(start));
+        self
+    }
+
+    /// Sets the strategy used to wait for the lock.
+    pub fn wait(&mut self, wait: Wait) -> &mut Self {
+        self.wait = wait;
+        self
+    }
+
+    /// Sets the [`Backend`] used to lock the file.
+    ///
+    /// [`Backend`]: enum.Backend.html
+    pub fn backend(&mut self, backend: Backend) -> &mut Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Sets whether a lock call interrupted by a signal is restarted.
+    ///
+    /// When `false`, an interrupted call fails with an `Error` of kind
+    /// `ErrorKind::Interrupted`, which allows a signal handler to cancel a
+    /// blocking wait.
+    pub fn retry_interrupted(&mut self, restart: bool) -> &mut Self {
+        self.restart = restart;
+        self
+    }
+
+    /// Acquires the lock on `file` with the options specified by `self`.
+    pub fn acquire<T: Deref<Target = File>>(&self, file: T) -> io::Result<FileGuard<T>> {
+        let id = next_lock_id();
+        let lock = self.claim(&file, id)?;
+        Ok(self.guard(file, lock, id))
+    }
+
+    /// Claims the lock with the id `id` with the options specified by `self`,
+    /// without creating a guard for it.
+    pub(crate) fn claim(&self, file: &File, id: u64) -> io::Result<Lock> {
+        match (self.wait, self.polling()) {
+            (Wait::Block, _) => self.block(file, id),
+            (_, Some((backoff, deadline))) => self.poll(file, id, backoff, deadline),
+            (_, None) => self.attempt(file, id),
+        }
+    }
+
+    /// Creates the guard for a lock with the id `id` that has been claimed
+    /// with these options.
+    pub(crate) fn guard<T: Deref<Target = File>>(
+        &self,
+        file: T,
+        lock: Lock,
+        id: u64,
+    ) -> FileGuard<T> {
+        FileGuard {
+            offset: self.offset,
+            len: self.len,
+            file,
+            lock,
+            backend: self.backend,
+            id,
+        }
+    }
+
+    /// Gets the backoff and deadline used to poll for the lock, or `None` if
+    /// the lock should not be polled.
+    pub(crate) fn polling(&self) -> Option<(Backoff, Option<Instant>)> {
+        self.wait.polling()
+    }
+
+    /// Gets the length passed to the raw lock functions, where zero extends
+    /// the lock to the end of the file and beyond.
+    pub(crate) fn raw_len(&self) -> u64 {
+        self.len.unwrap_or(0)
+    }
+
+    fn raw(&self, file: &File, id: u64, lock: Lock, wait: bool) -> io::Result<()> {
+        if self.len == Some(0) {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        let (off, len) = (self.offset, self.raw_len());
+        unsafe {
+            if self.restart {
+                raw_backend_lock(file, self.backend, id, Some(lock), off, len, wait)
+            } else {
+                raw_backend_lock_once(file, self.backend, id, Some(lock), off, len, wait)
+            }
+        }
+    }
+
+    fn block(&self, file: &File, id: u64) -> io::Result<Lock> {
+        match self.lock {
+            Some(lock) => self.raw(file, id, lock, true).map(|_| lock),
+            // Waiting for either lock type would block while the other may
+            // become available, so both are polled instead.
+            None => self.poll(file, id, Backoff::new(), None),
+        }
+    }
+
+    /// Makes a single attempt to claim the lock with the id `id` without
+    /// blocking.
+    pub(crate) fn attempt(&self, file: &File, id: u64) -> io::Result<Lock> {
+        match self.lock {
+            Some(lock) => self.raw(file, id, lock, false).map(|_| lock),
+            None => match self.raw(file, id, Lock::Exclusive, false) {
+                Ok(_) => Ok(Lock::Exclusive),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => self
+                    .raw(file, id, Lock::Shared, false)
+                    .map(|_| Lock::Shared),
+                Err(e) => Err(e),
+            },
+        }
+    }
+
+    fn poll(
+        &self,
+        file: &File,
+        id: u64,
+        mut backoff: Backoff,
+        deadline: Option<Instant>,
+    ) -> io::Result<Lock> {
+        loop {
+            match self.attempt(file, id) {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                    if !backoff.sleep_until(deadline) {
+                        return Err(ErrorKind::TimedOut.into());
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
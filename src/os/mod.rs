@@ -5,9 +5,23 @@ pub mod windows;
 #[cfg(windows)]
 pub use self::windows::{raw_file_lock, raw_file_downgrade};
 
+#[cfg(windows)]
+pub(crate) use self::windows::{
+    raw_backend_downgrade, raw_backend_extend, raw_backend_lock, raw_backend_lock_once,
+    raw_backend_merge, raw_backend_probe, raw_backend_shrink, raw_backend_split, raw_file_id,
+    raw_read_at, raw_write_at,
+};
+
 #[cfg(unix)]
 #[macro_use]
 pub mod unix;
 
 #[cfg(unix)]
 pub use self::unix::{raw_file_lock, raw_file_downgrade};
+
+#[cfg(unix)]
+pub(crate) use self::unix::{
+    raw_backend_downgrade, raw_backend_extend, raw_backend_lock, raw_backend_lock_once,
+    raw_backend_merge, raw_backend_probe, raw_backend_shrink, raw_backend_split, raw_file_id,
+    raw_read_at, raw_write_at,
+};
@@ -1,16 +1,55 @@
 //! Provides low-level support operations for file locking on UNIX platforms.
-use libc::{fcntl, off_t, F_RDLCK, F_SETLK, F_SETLKW, F_UNLCK, F_WRLCK, SEEK_SET};
+use libc::{fcntl, F_RDLCK, F_UNLCK, F_WRLCK, SEEK_SET};
+#[cfg(target_os = "linux")]
+use libc::{F_OFD_GETLK, F_OFD_SETLK, F_OFD_SETLKW};
 
 use std::fs::File;
 use std::io::{self, Error, ErrorKind};
-use std::ops::Deref;
-use std::os::raw::c_short;
+use std::mem;
+use std::ops::{Deref, Range};
+use std::os::raw::{c_int, c_short};
+use std::os::unix::fs::{FileExt, MetadataExt};
 use std::os::unix::io::AsRawFd;
+use std::thread;
 
-use crate::{FileGuard, Lock};
+// 32-bit Linux and Android may use a 32-bit `off_t`, so the `flock64`
+// commands are used to reach offsets beyond 2 GiB.
+#[cfg(all(
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "android"),
+    target_pointer_width = "32",
+    any(target_arch = "arm", target_arch = "x86", target_arch = "powerpc"),
+))]
+mod lfs {
+    use std::os::raw::c_int;
+
+    pub use libc::{flock64 as Flock, off64_t as off_t};
+
+    pub const F_GETLK: c_int = 12;
+    pub const F_SETLK: c_int = 13;
+    pub const F_SETLKW: c_int = 14;
+}
+
+#[cfg(not(all(
+    any(all(target_os = "linux", target_env = "gnu"), target_os = "android"),
+    target_pointer_width = "32",
+    any(target_arch = "arm", target_arch = "x86", target_arch = "powerpc"),
+)))]
+mod lfs {
+    pub use libc::{flock as Flock, off_t, F_GETLK, F_SETLK, F_SETLKW};
+}
+
+use self::lfs::{off_t, Flock, F_GETLK, F_SETLK, F_SETLKW};
+
+use crate::{is_deadlock, Backend, Conflict, FileGuard, Lock, UpgradeError, Wait};
+
+pub mod flock;
+mod registry;
 
 /// Acquires and releases a file lock.
 ///
+/// A `len` of zero extends the lock from `off` to the end of the file and
+/// beyond.
+///
 /// # Safety
 ///
 /// When used to unlock, this does not guarantee that an exclusive lock is
@@ -18,40 +57,61 @@ use crate::{FileGuard, Lock};
 pub unsafe fn raw_file_lock(
     f: &File,
     lock: Option<Lock>,
-    off: usize,
-    len: usize,
+    off: u64,
+    len: u64,
     wait: bool,
 ) -> io::Result<()> {
-    if len == 0 {
-        return Err(ErrorKind::InvalidInput.into());
-    }
-
     let op = match wait {
         true => F_SETLKW,
         false => F_SETLK,
     };
+    raw_fcntl_lock(f, op, lock, off, len, true)
+}
 
-    let lock = libc::flock {
-        l_start: off as off_t,
-        l_len: len as off_t,
-        l_pid: 0,
-        l_type: match lock {
-            Some(Lock::Shared) => F_RDLCK as c_short,
-            Some(Lock::Exclusive) => F_WRLCK as c_short,
-            None => F_UNLCK as c_short,
-        },
-        l_whence: SEEK_SET as c_short,
-        #[cfg(any(target_os = "freebsd", target_os = "solaris", target_os = "illumos"))]
-        l_sysid: 0,
-        #[cfg(any(target_os = "solaris", target_os = "illumos"))]
-        l_pad: [0; 4],
+/// Acquires and releases an open file description lock.
+///
+/// A `len` of zero extends the lock from `off` to the end of the file and
+/// beyond. Unlike the locks of [`raw_file_lock`], these are owned by the open file
+/// description rather than the process. They conflict between threads using
+/// separately opened files, and they are only released when the last file
+/// descriptor referring to the open file description is closed.
+///
+/// # Safety
+///
+/// When used to unlock, this does not guarantee that an exclusive lock is
+/// already held.
+///
+/// [`raw_file_lock`]: fn.raw_file_lock.html
+#[cfg(target_os = "linux")]
+pub unsafe fn raw_file_lock_ofd(
+    f: &File,
+    lock: Option<Lock>,
+    off: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<()> {
+    let op = match wait {
+        true => F_OFD_SETLKW,
+        false => F_OFD_SETLK,
     };
+    raw_fcntl_lock(f, op, lock, off, len, true)
+}
+
+unsafe fn raw_fcntl_lock(
+    f: &File,
+    op: c_int,
+    lock: Option<Lock>,
+    off: u64,
+    len: u64,
+    restart: bool,
+) -> io::Result<()> {
+    let lock = raw_flock(lock, off, len)?;
 
     loop {
         let rc = fcntl(f.as_raw_fd(), op, &lock);
         if rc == -1 {
             let err = Error::last_os_error();
-            if err.kind() != ErrorKind::Interrupted {
+            if !restart || err.kind() != ErrorKind::Interrupted {
                 break Err(err);
             }
         } else {
@@ -60,15 +120,339 @@ pub unsafe fn raw_file_lock(
     }
 }
 
+/// Gets the `fcntl` length of a byte range, where a range ending at
+/// `u64::MAX` has a length of zero to extend to the end of the file and
+/// beyond.
+fn range_len(range: &Range<u64>) -> u64 {
+    match range.end {
+        u64::MAX => 0,
+        end => end - range.start,
+    }
+}
+
+/// Gets the device and inode numbers that identify a file, regardless of
+/// how it was opened.
+pub(crate) fn raw_file_id(f: &File) -> io::Result<(u64, u64)> {
+    let meta = f.metadata()?;
+    Ok((meta.dev(), meta.ino()))
+}
+
+/// Reads bytes at an offset of a file without moving its cursor.
+///
+/// Bytes beyond the end of the file are read as zeros.
+pub(crate) fn raw_read_at(f: &File, mut buf: &mut [u8], mut off: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match f.read_at(buf, off) {
+            Ok(0) => break,
+            Ok(n) => {
+                buf = &mut buf[n..];
+                off += n as u64;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    buf.fill(0);
+    Ok(())
+}
+
+/// Writes bytes at an offset of a file without moving its cursor.
+pub(crate) fn raw_write_at(f: &File, buf: &[u8], off: u64) -> io::Result<()> {
+    f.write_all_at(buf, off)
+}
+
+fn raw_flock(lock: Option<Lock>, off: u64, len: u64) -> io::Result<Flock> {
+    match off.checked_add(len).map(off_t::try_from) {
+        Some(Ok(_)) => {}
+        _ => return Err(ErrorKind::InvalidInput.into()),
+    }
+
+    let mut fl: Flock = unsafe { mem::zeroed() };
+    fl.l_start = off as off_t;
+    fl.l_len = len as off_t;
+    fl.l_whence = SEEK_SET as c_short;
+    fl.l_type = match lock {
+        Some(Lock::Shared) => F_RDLCK as c_short,
+        Some(Lock::Exclusive) => F_WRLCK as c_short,
+        None => F_UNLCK as c_short,
+    };
+    Ok(fl)
+}
+
 /// Downgrades a file lock from exclusive to shared.
 ///
 /// # Safety
 ///
 /// This does not guarantee that an exclusive lock is already held.
-pub unsafe fn raw_file_downgrade(f: &File, off: usize, len: usize) -> io::Result<()> {
+pub unsafe fn raw_file_downgrade(f: &File, off: u64, len: u64) -> io::Result<()> {
     raw_file_lock(f, Some(Lock::Shared), off, len, false)
 }
 
+/// Sets whether guards of the [`Default`] backend detect deadlocks within a
+/// thread.
+///
+/// When enabled, waiting on a guard claimed by the same thread, or upgrading
+/// while the same thread holds an overlapping guard, fails with an `Error` of
+/// kind `ErrorKind::Deadlock` rather than blocking forever. A guard is owned
+/// by the thread that claimed it, even after it is sent to another thread, so
+/// a thread waiting on a guard it has given to another thread also fails.
+/// Only enable this when guards stay on the thread that claimed them.
+/// Detection is disabled by default, and applies to the whole process.
+///
+/// [`Default`]: ../../enum.Backend.html#variant.Default
+pub fn set_deadlock_detection(enabled: bool) {
+    registry::set_deadlock_detection(enabled);
+}
+
+/// Acquires and releases a file lock using the locks of a [`Backend`].
+///
+/// Locks of the [`Default`] backend are tracked by the process-wide registry
+/// under the id `id`, which must be released with the same id. Overlapping
+/// locks within the process conflict, and releasing a lock only unlocks the
+/// bytes no other lock still holds.
+///
+/// [`Backend`]: ../../enum.Backend.html
+/// [`Default`]: ../../enum.Backend.html#variant.Default
+pub(crate) unsafe fn raw_backend_lock(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    lock: Option<Lock>,
+    off: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<()> {
+    raw_backend_fcntl(f, backend, id, lock, off, len, wait, true)
+}
+
+/// Acquires and releases a file lock using the locks of a [`Backend`]
+/// without restarting the operation when interrupted by a signal.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_lock_once(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    lock: Option<Lock>,
+    off: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<()> {
+    raw_backend_fcntl(f, backend, id, lock, off, len, wait, false)
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn raw_backend_fcntl(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    lock: Option<Lock>,
+    off: u64,
+    len: u64,
+    wait: bool,
+    restart: bool,
+) -> io::Result<()> {
+    match (backend, lock) {
+        (Backend::Default, Some(lock)) => registry::lock(f, id, lock, off, len, wait, restart),
+        (Backend::Default, None) => registry::unlock(f, id, off, len),
+        #[cfg(target_os = "linux")]
+        (Backend::OpenFile, _) => {
+            let op = match wait {
+                true => F_OFD_SETLKW,
+                false => F_OFD_SETLK,
+            };
+            raw_fcntl_lock(f, op, lock, off, len, restart)
+        }
+        #[cfg(not(target_os = "linux"))]
+        (Backend::OpenFile, _) => Err(ErrorKind::Unsupported.into()),
+    }
+}
+
+/// Finds a lock that would prevent claiming a lock using the locks of a
+/// [`Backend`].
+///
+/// For the [`Default`] backend, the kernel does not report locks held by the
+/// calling process, so the guards of the process are found in the registry
+/// instead.
+///
+/// [`Backend`]: ../../enum.Backend.html
+/// [`Default`]: ../../enum.Backend.html#variant.Default
+pub(crate) unsafe fn raw_backend_probe(
+    f: &File,
+    backend: Backend,
+    lock: Lock,
+    off: u64,
+    len: u64,
+) -> io::Result<Option<Conflict>> {
+    let op = match backend {
+        Backend::Default => match registry::probe(f, lock, off, len)? {
+            Some(conflict) => return Ok(Some(conflict)),
+            None => F_GETLK,
+        },
+        #[cfg(target_os = "linux")]
+        Backend::OpenFile => F_OFD_GETLK,
+        #[cfg(not(target_os = "linux"))]
+        Backend::OpenFile => return Err(ErrorKind::Unsupported.into()),
+    };
+
+    let mut lock = raw_flock(Some(lock), off, len)?;
+    if fcntl(f.as_raw_fd(), op, &mut lock) == -1 {
+        return Err(Error::last_os_error());
+    }
+
+    if lock.l_type == F_UNLCK as c_short {
+        return Ok(None);
+    }
+
+    let start = lock.l_start as u64;
+    let end = match lock.l_len {
+        0 => u64::MAX,
+        n => start.saturating_add(n as u64),
+    };
+    Ok(Some(Conflict {
+        lock: if lock.l_type == F_WRLCK as c_short {
+            Lock::Exclusive
+        } else {
+            Lock::Shared
+        },
+        range: start..end,
+        pid: if lock.l_pid > 0 {
+            Some(lock.l_pid)
+        } else {
+            None
+        },
+    }))
+}
+
+/// Downgrades a file lock from exclusive to shared using the locks of a
+/// [`Backend`].
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_downgrade(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    off: u64,
+    len: u64,
+) -> io::Result<()> {
+    match backend {
+        Backend::Default => registry::downgrade(f, id, off, len),
+        _ => raw_backend_lock(f, backend, id, Some(Lock::Shared), off, len, false),
+    }
+}
+
+/// Upgrades a file lock from shared to exclusive using the locks of a
+/// [`Backend`].
+///
+/// For the [`Default`] backend, this first waits for the other guards of the
+/// process that overlap the lock, failing with `EDEADLK` if one of them is
+/// also upgrading.
+///
+/// [`Backend`]: ../../enum.Backend.html
+/// [`Default`]: ../../enum.Backend.html#variant.Default
+pub(crate) unsafe fn raw_backend_upgrade(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    off: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<()> {
+    match backend {
+        Backend::Default => registry::upgrade(f, id, off, len, wait, true),
+        _ => raw_backend_lock(f, backend, id, Some(Lock::Exclusive), off, len, wait),
+    }
+}
+
+/// Shrinks a held lock from the byte range `from` to the byte range `to`
+/// using the locks of a [`Backend`].
+///
+/// Ranges ending at `u64::MAX` extend to the end of the file and beyond.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_shrink(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    from: Range<u64>,
+    to: Range<u64>,
+) -> io::Result<()> {
+    if backend == Backend::Default {
+        return registry::shrink(f, id, from, to);
+    }
+
+    let mut result = Ok(());
+    for r in [from.start..to.start, to.end..from.end] {
+        if r.start < r.end {
+            result = result.and(raw_backend_lock(
+                f,
+                backend,
+                id,
+                None,
+                r.start,
+                range_len(&r),
+                false,
+            ));
+        }
+    }
+    result
+}
+
+/// Extends a held lock from the byte range `from` to the byte range `to`
+/// using the locks of a [`Backend`].
+///
+/// The whole range is locked in a single operation, so the bytes already
+/// held are never released, and on failure no new bytes are held.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_extend(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    lock: Lock,
+    from: Range<u64>,
+    to: Range<u64>,
+    wait: bool,
+) -> io::Result<()> {
+    match backend {
+        Backend::Default => registry::extend(f, id, lock, from, to, wait, true),
+        _ => raw_backend_lock(f, backend, id, Some(lock), to.start, range_len(&to), wait),
+    }
+}
+
+/// Merges the held lock with the id `other` into the held lock with the id
+/// `id`, on adjacent byte ranges, using the locks of a [`Backend`].
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_merge(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    other: u64,
+) -> io::Result<()> {
+    match backend {
+        Backend::Default => registry::merge(f, id, other),
+        _ => Ok(()),
+    }
+}
+
+/// Splits the held lock with the id `id` into two locks at the offset `at`
+/// using the locks of a [`Backend`], giving the second lock the id `tail`.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_split(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    at: u64,
+    tail: u64,
+) -> io::Result<()> {
+    match backend {
+        Backend::Default => registry::split(f, id, at, tail),
+        _ => Ok(()),
+    }
+}
+
 /// UNIX-specific extensions to [`FileGuard`].
 ///
 /// [`FileGuard`]: ../../struct.FileGuard.html
@@ -91,6 +475,44 @@ pub trait FileGuardExt {
     /// [`Shared`]: ../../enum.Lock.html#variant.Shared
     /// [`Exclusive`]: ../../enum.Lock.html#variant.Exclusive
     fn try_upgrade(&mut self) -> io::Result<()>;
+
+    /// Upgrades a lock from [`Shared`] to [`Exclusive`], recovering from
+    /// deadlocks with other upgrades.
+    ///
+    /// When two holders of a [`Shared`] lock both upgrade, each waits for the
+    /// other, and one of them fails with an `Error` of kind
+    /// `ErrorKind::Deadlock`. Rather than returning the error, the lock is
+    /// released so the other upgrade can complete, and after backing off, the
+    /// [`Shared`] lock is claimed again and the upgrade is retried. Because
+    /// the lock may be released between attempts, anything read while the
+    /// [`Shared`] lock was held should be read again after the upgrade.
+    ///
+    /// The guard is consumed and returned once upgraded. On failure, the
+    /// [`UpgradeError`] holds the error along with the guard, which still
+    /// holds its [`Shared`] lock. If the [`Shared`] lock could not be claimed
+    /// again, the guard no longer holds any lock, so it is dropped and the
+    /// [`UpgradeError`] holds no guard.
+    ///
+    /// The `wait` strategy controls the delay between attempts. With
+    /// [`Wait::Timeout`] or [`Wait::Deadline`], nothing blocks past the time:
+    /// each attempt is made as [`try_upgrade()`] does, so any attempt that
+    /// fails to upgrade releases the lock and is retried, and the [`Shared`]
+    /// lock is claimed again by polling. Once the time has passed, this fails
+    /// with an `Error` of kind `ErrorKind::TimedOut`. With [`Wait::Try`], this
+    /// is the same as [`try_upgrade()`]. Each attempt otherwise waits for the
+    /// lock as [`upgrade()`] does.
+    ///
+    /// [`Shared`]: ../../enum.Lock.html#variant.Shared
+    /// [`Exclusive`]: ../../enum.Lock.html#variant.Exclusive
+    /// [`UpgradeError`]: ../../struct.UpgradeError.html
+    /// [`Wait::Timeout`]: ../../enum.Wait.html#variant.Timeout
+    /// [`Wait::Deadline`]: ../../enum.Wait.html#variant.Deadline
+    /// [`Wait::Try`]: ../../enum.Wait.html#variant.Try
+    /// [`try_upgrade()`]: trait.FileGuardExt.html#tymethod.try_upgrade
+    /// [`upgrade()`]: trait.FileGuardExt.html#tymethod.upgrade
+    fn upgrade_with_retry(self, wait: Wait) -> Result<Self, UpgradeError<Self>>
+    where
+        Self: Sized;
 }
 
 impl<T> FileGuardExt for FileGuard<T>
@@ -100,13 +522,8 @@ where
     fn upgrade(&mut self) -> io::Result<()> {
         if self.is_shared() {
             unsafe {
-                raw_file_lock(
-                    &self.file,
-                    Some(Lock::Exclusive),
-                    self.offset,
-                    self.len,
-                    true,
-                )?;
+                let (id, off, len) = (self.id, self.offset, self.raw_len());
+                raw_backend_upgrade(&self.file, self.backend, id, off, len, true)?;
             }
             self.lock = Lock::Exclusive;
         }
@@ -116,16 +533,63 @@ where
     fn try_upgrade(&mut self) -> io::Result<()> {
         if self.is_shared() {
             unsafe {
-                raw_file_lock(
-                    &self.file,
-                    Some(Lock::Exclusive),
-                    self.offset,
-                    self.len,
-                    false,
-                )?;
+                let (id, off, len) = (self.id, self.offset, self.raw_len());
+                raw_backend_upgrade(&self.file, self.backend, id, off, len, false)?;
             }
             self.lock = Lock::Exclusive;
         }
         Ok(())
     }
+
+    fn upgrade_with_retry(mut self, wait: Wait) -> Result<Self, UpgradeError<Self>> {
+        if !self.is_shared() {
+            return Ok(self);
+        }
+        let (mut backoff, deadline) = match wait.polling() {
+            Some(polling) => polling,
+            None => {
+                return match self.try_upgrade() {
+                    Ok(()) => Ok(self),
+                    Err(e) => Err(UpgradeError::new(Some(self), e)),
+                }
+            }
+        };
+
+        // With a deadline, no attempt may block, so any failed attempt is
+        // treated as a possible deadlock.
+        let shared = match deadline {
+            Some(deadline) => Wait::Deadline(deadline),
+            None => Wait::Block,
+        };
+        let shared = self
+            .backend
+            .options(Some(Lock::Shared), self.offset, self.len, shared);
+        let (id, off, len) = (self.id, self.offset, self.raw_len());
+        loop {
+            let result = match deadline {
+                Some(_) => self.try_upgrade(),
+                None => self.upgrade(),
+            };
+            match result {
+                Ok(()) => return Ok(self),
+                Err(e) if is_deadlock(&e) || e.kind() == ErrorKind::WouldBlock => {}
+                Err(e) => return Err(UpgradeError::new(Some(self), e)),
+            }
+            let delay = match backoff.delay_until(deadline) {
+                Some(delay) => delay,
+                None => return Err(UpgradeError::new(Some(self), ErrorKind::TimedOut.into())),
+            };
+            let released =
+                unsafe { raw_backend_lock(&self.file, self.backend, id, None, off, len, false) };
+            if let Err(e) = released {
+                return Err(UpgradeError::new(Some(self), e));
+            }
+            thread::sleep(delay);
+            if let Err(e) = shared.claim(&self.file, id) {
+                // The guard holds no lock, so it is dropped without unlocking.
+                self.into_id();
+                return Err(UpgradeError::new(None, e));
+            }
+        }
+    }
 }
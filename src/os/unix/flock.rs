@@ -0,0 +1,253 @@
+//! Provides whole-file locking using `flock(2)`.
+//!
+//! These locks are independent of the `fcntl` locks used by the rest of the
+//! crate. A `flock` lock never conflicts with a byte range lock, but it does
+//! conflict with other `flock` users such as `flock(1)` or Cargo. This allows
+//! Rust programs and shell scripts to participate in the same protocol.
+//!
+//! `flock` locks are owned by the open file description, so separately
+//! opened `File` objects exclude each other even within the same process.
+//!
+//! # Examples
+//!
+//! ```
+//! use file_guard::Lock;
+//! use file_guard::os::unix::flock;
+//! use std::fs::OpenOptions;
+//!
+//! # fn main() -> std::io::Result<()> {
+//! let file = OpenOptions::new()
+//!     .read(true)
+//!     .write(true)
+//!     .create(true)
+//!     .open("example-lock")?;
+//!
+//! let mut lock = flock::lock(&file, Lock::Exclusive)?;
+//! lock.downgrade()?;
+//! // the lock will be unlocked when it goes out of scope
+//! # Ok(())
+//! # }
+//! ```
+use libc::{LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN};
+
+use std::fs::File;
+use std::io::{Error, ErrorKind};
+use std::ops::{Deref, DerefMut};
+use std::os::unix::io::AsRawFd;
+use std::{fmt, io};
+
+use crate::{Lock, UpgradeError};
+
+/// Acquires and releases a whole-file `flock` lock.
+///
+/// # Safety
+///
+/// When used to unlock, this does not guarantee that a lock is already held.
+pub unsafe fn raw_file_flock(f: &File, lock: Option<Lock>, wait: bool) -> io::Result<()> {
+    let mut op = match lock {
+        Some(Lock::Shared) => LOCK_SH,
+        Some(Lock::Exclusive) => LOCK_EX,
+        None => LOCK_UN,
+    };
+    if !wait {
+        op |= LOCK_NB;
+    }
+
+    loop {
+        let rc = libc::flock(f.as_raw_fd(), op);
+        if rc == -1 {
+            let err = Error::last_os_error();
+            if err.kind() != ErrorKind::Interrupted {
+                break Err(err);
+            }
+        } else {
+            break Ok(());
+        }
+    }
+}
+
+/// Wait and claim the desired [`Lock`] type for the whole file.
+///
+/// [`Lock`]: ../../../enum.Lock.html
+pub fn lock<T: Deref<Target = File>>(file: T, lock: Lock) -> io::Result<FlockGuard<T>> {
+    unsafe {
+        raw_file_flock(&file, Some(lock), true)?;
+    }
+    Ok(FlockGuard { file, lock })
+}
+
+/// Attempt to claim the desired [`Lock`] type for the whole file.
+///
+/// If the desired [`Lock`] type cannot be obtained without blocking, an
+/// `Error` of kind `ErrorKind::WouldBlock` is returned. Otherwise if
+/// successful, the lock is held.
+///
+/// [`Lock`]: ../../../enum.Lock.html
+pub fn try_lock<T: Deref<Target = File>>(file: T, lock: Lock) -> io::Result<FlockGuard<T>> {
+    unsafe {
+        raw_file_flock(&file, Some(lock), false)?;
+    }
+    Ok(FlockGuard { file, lock })
+}
+
+/// An RAII implementation of a "scoped lock" of a whole file using `flock`.
+/// When this structure is dropped (falls out of scope), the lock will be
+/// unlocked.
+///
+/// This structure is created by the [`lock()`] and [`try_lock()`] functions.
+///
+/// [`lock()`]: fn.lock.html
+/// [`try_lock()`]: fn.try_lock.html
+#[must_use = "if unused the file lock will immediately unlock"]
+pub struct FlockGuard<T: Deref<Target = File>> {
+    file: T,
+    lock: Lock,
+}
+
+impl<T> fmt::Debug for FlockGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FlockGuard::{:?}", self.lock)
+    }
+}
+
+impl<T> FlockGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Gets the [`Lock`] type currently held.
+    ///
+    /// [`Lock`]: ../../../enum.Lock.html
+    #[inline]
+    pub fn lock_type(&self) -> Lock {
+        self.lock
+    }
+
+    /// Test if the currently held [`Lock`] type is [`Shared`].
+    ///
+    /// [`Lock`]: ../../../enum.Lock.html
+    /// [`Shared`]: ../../../enum.Lock.html#variant.Shared
+    #[inline]
+    pub fn is_shared(&self) -> bool {
+        self.lock == Lock::Shared
+    }
+
+    /// Test if the currently held [`Lock`] type is [`Exclusive`].
+    ///
+    /// [`Lock`]: ../../../enum.Lock.html
+    /// [`Exclusive`]: ../../../enum.Lock.html#variant.Exclusive
+    #[inline]
+    pub fn is_exclusive(&self) -> bool {
+        self.lock == Lock::Exclusive
+    }
+
+    /// Exchanges an [`Exclusive`] [`Lock`] for a [`Shared`] one.
+    ///
+    /// If the currently held lock is already [`Shared`], no change is made and
+    /// the method succeeds. Unlike [`FileGuard::downgrade`], `flock` does not
+    /// guarantee this conversion is atomic, so a waiting [`Exclusive`] lock
+    /// attempt may obtain the lock during the downgrade.
+    ///
+    /// [`Lock`]: ../../../enum.Lock.html
+    /// [`Exclusive`]: ../../../enum.Lock.html#variant.Exclusive
+    /// [`Shared`]: ../../../enum.Lock.html#variant.Shared
+    /// [`FileGuard::downgrade`]: ../../../struct.FileGuard.html#method.downgrade
+    pub fn downgrade(&mut self) -> io::Result<()> {
+        if self.is_exclusive() {
+            unsafe {
+                raw_file_flock(&self.file, Some(Lock::Shared), true)?;
+            }
+            self.lock = Lock::Shared;
+        }
+        Ok(())
+    }
+
+    /// Upgrades a lock from [`Shared`] to [`Exclusive`].
+    ///
+    /// If the currently held lock is already [`Exclusive`], no change is made
+    /// and the method succeeds. `flock` does not guarantee this conversion is
+    /// atomic, so another process may obtain an [`Exclusive`] lock during the
+    /// upgrade.
+    ///
+    /// The guard is consumed and returned once upgraded. If the upgrade
+    /// fails, the [`Shared`] lock is claimed again, and the [`UpgradeError`]
+    /// holds the error along with the guard. If the [`Shared`] lock cannot be
+    /// claimed again, the guard no longer holds any lock, so it is dropped
+    /// and the [`UpgradeError`] holds no guard.
+    ///
+    /// [`Shared`]: ../../../enum.Lock.html#variant.Shared
+    /// [`Exclusive`]: ../../../enum.Lock.html#variant.Exclusive
+    /// [`UpgradeError`]: ../../../struct.UpgradeError.html
+    pub fn upgrade(self) -> Result<Self, UpgradeError<Self>> {
+        self.raw_upgrade(true)
+    }
+
+    /// Attempts to upgrade a lock from [`Shared`] to [`Exclusive`].
+    ///
+    /// If the currently held lock is already [`Exclusive`], no change is made
+    /// and the method succeeds. If the upgrade cannot be obtained without
+    /// blocking, an [`UpgradeError`] holding an `Error` of kind
+    /// `ErrorKind::WouldBlock` is returned.
+    ///
+    /// As with [`upgrade()`], the conversion is not atomic. `flock` releases
+    /// the [`Shared`] lock before attempting the [`Exclusive`] one, so after a
+    /// failed attempt the [`Shared`] lock is claimed again without blocking.
+    /// If another process obtained an [`Exclusive`] lock in between, the
+    /// guard no longer holds any lock, so the [`UpgradeError`] holds no guard.
+    ///
+    /// [`Shared`]: ../../../enum.Lock.html#variant.Shared
+    /// [`Exclusive`]: ../../../enum.Lock.html#variant.Exclusive
+    /// [`UpgradeError`]: ../../../struct.UpgradeError.html
+    /// [`upgrade()`]: #method.upgrade
+    pub fn try_upgrade(self) -> Result<Self, UpgradeError<Self>> {
+        self.raw_upgrade(false)
+    }
+
+    fn raw_upgrade(mut self, wait: bool) -> Result<Self, UpgradeError<Self>> {
+        if self.is_shared() {
+            unsafe {
+                if let Err(e) = raw_file_flock(&self.file, Some(Lock::Exclusive), wait) {
+                    // A guard that could not claim its lock again holds nothing.
+                    return match raw_file_flock(&self.file, Some(Lock::Shared), wait) {
+                        Ok(()) => Err(UpgradeError::new(Some(self), e)),
+                        Err(_) => Err(UpgradeError::new(None, e)),
+                    };
+                }
+            }
+            self.lock = Lock::Exclusive;
+        }
+        Ok(self)
+    }
+}
+
+impl<T> Deref for FlockGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.file
+    }
+}
+
+impl<T> DerefMut for FlockGuard<T>
+where
+    T: DerefMut<Target = File>,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.file
+    }
+}
+
+impl<T> Drop for FlockGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    #[inline]
+    fn drop(&mut self) {
+        let _ = unsafe { raw_file_flock(&self.file, None, false) };
+    }
+}
@@ -0,0 +1,439 @@
+//! Tracks the byte ranges held by the live guards of the process.
+//!
+//! Classic `fcntl` locks are owned by the process, so the locks of
+//! overlapping guards merge, and unlocking or downgrading one guard changes
+//! the bytes of every other guard. The registry records the range and lock
+//! type of each guard using the [`Default`] backend, keyed by the device and
+//! inode of the file. Guards may hold identical ranges, so each is found by
+//! the unique id of its lock rather than by its range. Conflicting guards
+//! within the process then wait or fail as they would between processes, and
+//! only bytes that no other guard still holds are ever unlocked.
+//!
+//! Each guard also records the thread that claimed it. When deadlock
+//! detection is enabled, a guard that would wait on one claimed by the same
+//! thread fails with `EDEADLK` instead, since it could never be released.
+//! A guard may be sent to another thread without the registry knowing, so
+//! this is only enabled on request.
+//!
+//! [`Default`]: ../../../enum.Backend.html#variant.Default
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Error, ErrorKind};
+use std::ops::Range;
+use std::os::raw::c_int;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread::{self, ThreadId};
+
+use super::{range_len, raw_fcntl_lock, raw_file_id, F_SETLK, F_SETLKW};
+use crate::{Conflict, Lock};
+
+type Key = (u64, u64);
+
+struct Held {
+    id: u64,
+    lock: Lock,
+    range: Range<u64>,
+    thread: ThreadId,
+    upgrading: bool,
+}
+
+impl Held {
+    fn overlaps(&self, range: &Range<u64>) -> bool {
+        self.range.start < range.end && range.start < self.range.end
+    }
+
+    fn conflicts(&self, lock: Lock, range: &Range<u64>) -> bool {
+        self.overlaps(range) && (lock == Lock::Exclusive || self.lock == Lock::Exclusive)
+    }
+}
+
+struct Registry {
+    files: Mutex<HashMap<Key, Vec<Held>>>,
+    cond: Condvar,
+}
+
+type Files<'a> = MutexGuard<'a, HashMap<Key, Vec<Held>>>;
+
+impl Registry {
+    fn lock(&self) -> Files<'_> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn wait<'a>(&self, files: Files<'a>) -> Files<'a> {
+        self.cond.wait(files).unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| Registry {
+        files: Mutex::new(HashMap::new()),
+        cond: Condvar::new(),
+    })
+}
+
+static DETECT_DEADLOCKS: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether waiting on a guard claimed by the same thread fails with
+/// `EDEADLK`.
+pub(crate) fn set_deadlock_detection(enabled: bool) {
+    DETECT_DEADLOCKS.store(enabled, Ordering::Relaxed);
+}
+
+/// Checks if a guard claimed by the thread `thread` would be waited on by the
+/// calling thread, while deadlock detection is enabled.
+fn is_own(thread: ThreadId) -> bool {
+    DETECT_DEADLOCKS.load(Ordering::Relaxed) && thread == thread::current().id()
+}
+
+fn key(f: &File) -> io::Result<Key> {
+    raw_file_id(f)
+}
+
+fn range(off: u64, len: u64) -> Range<u64> {
+    match len {
+        0 => off..u64::MAX,
+        n => off..off.saturating_add(n),
+    }
+}
+
+fn op(wait: bool) -> c_int {
+    match wait {
+        true => F_SETLKW,
+        false => F_SETLK,
+    }
+}
+
+/// Claims a lock with the id `id`, first waiting for any conflicting guard of
+/// the process.
+pub(crate) unsafe fn lock(
+    f: &File,
+    id: u64,
+    lock: Lock,
+    off: u64,
+    len: u64,
+    wait: bool,
+    restart: bool,
+) -> io::Result<()> {
+    let key = key(f)?;
+    let range = range(off, len);
+    let reg = registry();
+
+    let mut files = reg.lock();
+    while let Some(own) = conflict(&files, key, id, lock, &range) {
+        if !wait {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        if own {
+            return Err(Error::from_raw_os_error(libc::EDEADLK));
+        }
+        files = reg.wait(files);
+    }
+    files.entry(key).or_default().push(Held {
+        id,
+        lock,
+        range,
+        thread: thread::current().id(),
+        upgrading: false,
+    });
+    drop(files);
+
+    // The range is reserved, so the registry is not held while waiting on
+    // other processes.
+    let result = raw_fcntl_lock(f, op(wait), Some(lock), off, len, restart);
+    if result.is_err() {
+        let mut files = reg.lock();
+        let _ = release(&mut files, f, key, id);
+        reg.cond.notify_all();
+    }
+    result
+}
+
+/// Releases the lock with the id `id`, unlocking only the bytes no other
+/// guard holds.
+///
+/// A lock missing from the registry is unlocked by its byte range. If the
+/// unlock fails, the lock remains in the registry so it may be retried.
+pub(crate) unsafe fn unlock(f: &File, id: u64, off: u64, len: u64) -> io::Result<()> {
+    let key = key(f)?;
+    let reg = registry();
+
+    let mut files = reg.lock();
+    let result = match take(&mut files, key, id) {
+        Some(held) => {
+            let result = unlock_uncovered(&mut files, f, key, &held.range);
+            if result.is_err() {
+                files.entry(key).or_default().push(held);
+            }
+            result
+        }
+        None => unlock_uncovered(&mut files, f, key, &range(off, len)),
+    };
+    reg.cond.notify_all();
+    result
+}
+
+/// Downgrades the exclusive lock with the id `id` to a shared lock.
+pub(crate) unsafe fn downgrade(f: &File, id: u64, off: u64, len: u64) -> io::Result<()> {
+    let key = key(f)?;
+    let reg = registry();
+
+    // No other guard may overlap an exclusive lock, so the whole range can be
+    // downgraded.
+    let mut files = reg.lock();
+    raw_fcntl_lock(f, F_SETLK, Some(Lock::Shared), off, len, true)?;
+    if let Some(h) = find(&mut files, key, id) {
+        h.lock = Lock::Shared;
+    }
+    reg.cond.notify_all();
+    Ok(())
+}
+
+/// Upgrades the shared lock with the id `id` to an exclusive lock, first
+/// waiting for the other guards of the process that overlap it.
+///
+/// If an overlapping guard is also waiting to upgrade, or deadlock detection
+/// is enabled and it would be waited on by the thread that claimed it,
+/// neither can proceed, so this fails with `EDEADLK`.
+pub(crate) unsafe fn upgrade(
+    f: &File,
+    id: u64,
+    off: u64,
+    len: u64,
+    wait: bool,
+    restart: bool,
+) -> io::Result<()> {
+    let key = key(f)?;
+    let range = range(off, len);
+    let reg = registry();
+
+    let mut files = reg.lock();
+    match find(&mut files, key, id) {
+        Some(h) if h.lock == Lock::Shared => h.upgrading = true,
+        _ => return raw_fcntl_lock(f, op(wait), Some(Lock::Exclusive), off, len, restart),
+    }
+    // Only the owner of a lock releases it, so the entry remains while it is
+    // upgrading.
+    while let Some(held) = files.get_mut(&key) {
+        let pos = match held.iter().position(|h| h.id == id) {
+            Some(pos) => pos,
+            None => break,
+        };
+        let mut others = held
+            .iter()
+            .enumerate()
+            .filter(|&(i, h)| i != pos && h.overlaps(&range))
+            .map(|(_, h)| h)
+            .peekable();
+
+        if others.peek().is_none() {
+            held[pos].lock = Lock::Exclusive;
+            held[pos].upgrading = false;
+            break;
+        }
+        let deadlock = others.any(|h| h.upgrading || (wait && is_own(h.thread)));
+        if deadlock || !wait {
+            held[pos].upgrading = false;
+            reg.cond.notify_all();
+            return Err(match deadlock {
+                true => Error::from_raw_os_error(libc::EDEADLK),
+                false => ErrorKind::WouldBlock.into(),
+            });
+        }
+        files = reg.wait(files);
+    }
+    drop(files);
+
+    let result = raw_fcntl_lock(f, op(wait), Some(Lock::Exclusive), off, len, restart);
+    if result.is_err() {
+        // A failed upgrade leaves the shared lock held.
+        let mut files = reg.lock();
+        if let Some(h) = find(&mut files, key, id) {
+            h.lock = Lock::Shared;
+        }
+        reg.cond.notify_all();
+    }
+    result
+}
+
+/// Shrinks the range of the lock with the id `id`, unlocking the bytes it no
+/// longer covers unless another guard still holds them.
+pub(crate) unsafe fn shrink(f: &File, id: u64, from: Range<u64>, to: Range<u64>) -> io::Result<()> {
+    let key = key(f)?;
+    let reg = registry();
+
+    let mut files = reg.lock();
+    if let Some(h) = find(&mut files, key, id) {
+        h.range = to.clone();
+    }
+    let prefix = unlock_uncovered(&mut files, f, key, &(from.start..to.start));
+    let suffix = unlock_uncovered(&mut files, f, key, &(to.end..from.end));
+    reg.cond.notify_all();
+    prefix.and(suffix)
+}
+
+/// Extends the range of the lock with the id `id`, first waiting for any
+/// other guard of the process that conflicts with the new range.
+pub(crate) unsafe fn extend(
+    f: &File,
+    id: u64,
+    lock: Lock,
+    from: Range<u64>,
+    to: Range<u64>,
+    wait: bool,
+    restart: bool,
+) -> io::Result<()> {
+    let key = key(f)?;
+    let reg = registry();
+
+    let mut files = reg.lock();
+    while let Some(own) = conflict(&files, key, id, lock, &to) {
+        if !wait {
+            return Err(ErrorKind::WouldBlock.into());
+        }
+        if own {
+            return Err(Error::from_raw_os_error(libc::EDEADLK));
+        }
+        files = reg.wait(files);
+    }
+    if let Some(h) = find(&mut files, key, id) {
+        h.range = to.clone();
+    }
+    drop(files);
+
+    let result = raw_fcntl_lock(f, op(wait), Some(lock), to.start, range_len(&to), restart);
+    if result.is_err() {
+        let mut files = reg.lock();
+        if let Some(h) = find(&mut files, key, id) {
+            h.range = from.clone();
+        }
+        let _ = unlock_uncovered(&mut files, f, key, &(to.start..from.start));
+        let _ = unlock_uncovered(&mut files, f, key, &(from.end..to.end));
+        reg.cond.notify_all();
+    }
+    result
+}
+
+/// Merges the lock with the id `other` into the adjacent lock with the id
+/// `id`, which takes on the ranges of both.
+pub(crate) fn merge(f: &File, id: u64, other: u64) -> io::Result<()> {
+    let key = key(f)?;
+
+    let mut files = registry().lock();
+    if let Some(held) = files.get_mut(&key) {
+        let pa = held.iter().position(|h| h.id == id);
+        let pb = held.iter().position(|h| h.id == other);
+        if let (Some(pa), Some(pb)) = (pa, pb) {
+            let b = held.remove(pb).range;
+            let a = &mut held[if pb < pa { pa - 1 } else { pa }].range;
+            *a = a.start.min(b.start)..a.end.max(b.end);
+        }
+    }
+    Ok(())
+}
+
+/// Splits the range of the lock with the id `id` at the offset `at`, giving
+/// the bytes from `at` onwards to a new lock with the id `tail`.
+pub(crate) fn split(f: &File, id: u64, at: u64, tail: u64) -> io::Result<()> {
+    let key = key(f)?;
+
+    let mut files = registry().lock();
+    if let Some(h) = find(&mut files, key, id) {
+        let held = Held {
+            id: tail,
+            lock: h.lock,
+            range: at..h.range.end,
+            thread: h.thread,
+            upgrading: false,
+        };
+        h.range.end = at;
+        files.entry(key).or_default().push(held);
+    }
+    Ok(())
+}
+
+/// Finds a guard of the process that would prevent claiming a lock.
+pub(crate) fn probe(f: &File, lock: Lock, off: u64, len: u64) -> io::Result<Option<Conflict>> {
+    let key = key(f)?;
+    let range = range(off, len);
+
+    let files = registry().lock();
+    Ok(files.get(&key).and_then(|held| {
+        held.iter()
+            .find(|h| h.conflicts(lock, &range))
+            .map(|h| Conflict {
+                lock: h.lock,
+                range: h.range.clone(),
+                pid: Some(process::id() as i32),
+            })
+    }))
+}
+
+/// Checks for a guard other than the lock with the id `id` that conflicts
+/// with a lock of `range`, and if there is one, whether deadlock detection
+/// finds any such guard was claimed by the calling thread.
+fn conflict(files: &Files<'_>, key: Key, id: u64, lock: Lock, range: &Range<u64>) -> Option<bool> {
+    let held = files.get(&key)?;
+    let mut conflicts = held
+        .iter()
+        .filter(|h| h.id != id && h.conflicts(lock, range))
+        .peekable();
+    conflicts.peek()?;
+    Some(conflicts.any(|h| is_own(h.thread)))
+}
+
+fn find<'a>(files: &'a mut Files<'_>, key: Key, id: u64) -> Option<&'a mut Held> {
+    files.get_mut(&key)?.iter_mut().find(|h| h.id == id)
+}
+
+/// Removes the lock with the id `id` from the registry and unlocks the parts
+/// of its range that are not held by any other guard.
+unsafe fn release(files: &mut Files<'_>, f: &File, key: Key, id: u64) -> io::Result<()> {
+    match take(files, key, id) {
+        Some(held) => unlock_uncovered(files, f, key, &held.range),
+        None => Ok(()),
+    }
+}
+
+/// Removes the lock with the id `id` from the registry without unlocking it.
+fn take(files: &mut Files<'_>, key: Key, id: u64) -> Option<Held> {
+    let held = files.get_mut(&key)?;
+    let pos = held.iter().position(|h| h.id == id)?;
+    Some(held.remove(pos))
+}
+
+/// Unlocks the parts of a range that are not held by any guard.
+unsafe fn unlock_uncovered(
+    files: &mut Files<'_>,
+    f: &File,
+    key: Key,
+    range: &Range<u64>,
+) -> io::Result<()> {
+    let mut covered: Vec<Range<u64>> = Vec::new();
+    if let Some(held) = files.get(&key) {
+        covered.extend(
+            held.iter()
+                .filter(|h| h.overlaps(range))
+                .map(|h| h.range.start.max(range.start)..h.range.end.min(range.end)),
+        );
+        if held.is_empty() {
+            files.remove(&key);
+        }
+    }
+    covered.sort_by_key(|r| r.start);
+
+    let mut result = Ok(());
+    let mut start = range.start;
+    for r in covered.iter().chain(Some(&(range.end..range.end))) {
+        if start < r.start {
+            let len = range_len(&(start..r.start));
+            if let Err(e) = raw_fcntl_lock(f, F_SETLK, None, start, len, true) {
+                result = result.and(Err(e));
+            }
+        }
+        start = start.max(r.end);
+    }
+    result
+}
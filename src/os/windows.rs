@@ -2,19 +2,25 @@
 use std::fs::File;
 use std::io::{self, Error, ErrorKind};
 use std::mem::MaybeUninit;
-use std::ops::Deref;
+use std::ops::{Deref, Range};
+use std::os::windows::fs::FileExt;
 use std::os::windows::io::AsRawHandle;
 
 use winapi::shared::minwindef::DWORD;
 use winapi::shared::winerror::ERROR_LOCK_VIOLATION;
-use winapi::um::fileapi::{LockFileEx, UnlockFileEx};
+use winapi::um::fileapi::{
+    GetFileInformationByHandle, LockFileEx, UnlockFileEx, BY_HANDLE_FILE_INFORMATION,
+};
 use winapi::um::minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED};
 use winapi::um::winnt::HANDLE;
 
-use crate::{FileGuard, Lock};
+use crate::{Backend, Conflict, FileGuard, Lock};
 
 /// Acquires and releases a file lock.
 ///
+/// A `len` of zero extends the lock from `off` to the end of the file and
+/// beyond.
+///
 /// # Safety
 ///
 /// When used to unlock, this does not guarantee that an exclusive lock is
@@ -22,21 +28,25 @@ use crate::{FileGuard, Lock};
 pub unsafe fn raw_file_lock(
     f: &File,
     lock: Option<Lock>,
-    off: usize,
-    len: usize,
+    off: u64,
+    len: u64,
     wait: bool,
 ) -> io::Result<()> {
-    if len == 0 {
+    let len = match len {
+        0 => u64::MAX - off,
+        n => n,
+    };
+    if len == 0 || off.checked_add(len).is_none() {
         return Err(ErrorKind::InvalidInput.into());
     }
 
     let mut ov: OVERLAPPED = MaybeUninit::zeroed().assume_init();
     let s = ov.u.s_mut();
     s.Offset = (off & 0xffffffff) as DWORD;
-    s.OffsetHigh = (off >> 16 >> 16) as DWORD;
+    s.OffsetHigh = (off >> 32) as DWORD;
 
     let lenlow = (len & 0xffffffff) as DWORD;
-    let lenhigh = (len >> 16 >> 16) as DWORD;
+    let lenhigh = (len >> 32) as DWORD;
 
     let rc = if let Some(lock) = lock {
         let mut flags = if wait { 0 } else { LOCKFILE_FAIL_IMMEDIATELY };
@@ -60,18 +70,248 @@ pub unsafe fn raw_file_lock(
     }
 }
 
+/// Gets the volume serial number and file index that identify a file,
+/// regardless of how it was opened.
+pub(crate) fn raw_file_id(f: &File) -> io::Result<(u64, u64)> {
+    let mut info: BY_HANDLE_FILE_INFORMATION = unsafe { MaybeUninit::zeroed().assume_init() };
+    if unsafe { GetFileInformationByHandle(f.as_raw_handle() as HANDLE, &mut info) } == 0 {
+        return Err(Error::last_os_error());
+    }
+    let index = (info.nFileIndexHigh as u64) << 32 | info.nFileIndexLow as u64;
+    Ok((info.dwVolumeSerialNumber as u64, index))
+}
+
+/// Reads bytes at an offset of a file.
+///
+/// Bytes beyond the end of the file are read as zeros. The cursor of the file
+/// is moved to the end of the bytes read.
+pub(crate) fn raw_read_at(f: &File, mut buf: &mut [u8], mut off: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match f.seek_read(buf, off) {
+            Ok(0) => break,
+            Ok(n) => {
+                buf = &mut buf[n..];
+                off += n as u64;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    buf.fill(0);
+    Ok(())
+}
+
+/// Writes bytes at an offset of a file.
+///
+/// The cursor of the file is moved to the end of the bytes written.
+pub(crate) fn raw_write_at(f: &File, mut buf: &[u8], mut off: u64) -> io::Result<()> {
+    while !buf.is_empty() {
+        match f.seek_write(buf, off) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(n) => {
+                buf = &buf[n..];
+                off += n as u64;
+            }
+            Err(e) if e.kind() == ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
 /// Downgrades a file lock from exclusive to shared.
 ///
 /// # Safety
 ///
 /// This does not guarantee that an exclusive lock is already held.
-pub unsafe fn raw_file_downgrade(f: &File, off: usize, len: usize) -> io::Result<()> {
+pub unsafe fn raw_file_downgrade(f: &File, off: u64, len: u64) -> io::Result<()> {
     // Add a shared lock.
     raw_file_lock(f, Some(Lock::Shared), off, len, false)?;
     // Removed the exclusive lock.
     raw_file_lock(f, None, off, len, false)
 }
 
+/// Acquires and releases a file lock using the locks of a [`Backend`].
+///
+/// Locks taken with `LockFileEx` are already owned by the file handle, so
+/// every [`Backend`] uses the same underlying lock, and the id of the lock is
+/// not needed.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_lock(
+    f: &File,
+    _backend: Backend,
+    _id: u64,
+    lock: Option<Lock>,
+    off: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<()> {
+    raw_file_lock(f, lock, off, len, wait)
+}
+
+/// Acquires and releases a file lock using the locks of a [`Backend`]
+/// without restarting the operation when interrupted.
+///
+/// Windows locks are never interrupted, so this is the same as
+/// [`raw_backend_lock`].
+///
+/// [`Backend`]: ../../enum.Backend.html
+/// [`raw_backend_lock`]: fn.raw_backend_lock.html
+pub(crate) unsafe fn raw_backend_lock_once(
+    f: &File,
+    backend: Backend,
+    id: u64,
+    lock: Option<Lock>,
+    off: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<()> {
+    raw_backend_lock(f, backend, id, lock, off, len, wait)
+}
+
+/// Finds a lock that would prevent claiming a lock using the locks of a
+/// [`Backend`].
+///
+/// Windows cannot query existing locks, so this briefly claims and releases
+/// the requested lock instead. The conflicting range is reported as the
+/// requested range, and the process holding the lock is never known.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_probe(
+    f: &File,
+    _backend: Backend,
+    lock: Lock,
+    off: u64,
+    len: u64,
+) -> io::Result<Option<Conflict>> {
+    let conflict = |lock| {
+        Some(Conflict {
+            lock,
+            range: match len {
+                0 => off..u64::MAX,
+                n => off..off.saturating_add(n),
+            },
+            pid: None,
+        })
+    };
+
+    match raw_file_lock(f, Some(lock), off, len, false) {
+        Ok(_) => {
+            raw_file_lock(f, None, off, len, false)?;
+            return Ok(None);
+        }
+        Err(e) if e.kind() != ErrorKind::WouldBlock => return Err(e),
+        Err(_) => {}
+    }
+
+    if lock == Lock::Shared {
+        return Ok(conflict(Lock::Exclusive));
+    }
+
+    match raw_file_lock(f, Some(Lock::Shared), off, len, false) {
+        Ok(_) => {
+            raw_file_lock(f, None, off, len, false)?;
+            Ok(conflict(Lock::Shared))
+        }
+        Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(conflict(Lock::Exclusive)),
+        Err(e) => Err(e),
+    }
+}
+
+/// Downgrades a file lock from exclusive to shared using the locks of a
+/// [`Backend`].
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_downgrade(
+    f: &File,
+    _backend: Backend,
+    _id: u64,
+    off: u64,
+    len: u64,
+) -> io::Result<()> {
+    raw_file_downgrade(f, off, len)
+}
+
+/// Shrinks a held lock from the byte range `from` to the byte range `to`
+/// using the locks of a [`Backend`].
+///
+/// Windows can only unlock a region exactly as it was locked, so this fails
+/// with an `Error` of kind `ErrorKind::Unsupported` unless the ranges are
+/// equal.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_shrink(
+    _f: &File,
+    _backend: Backend,
+    _id: u64,
+    from: Range<u64>,
+    to: Range<u64>,
+) -> io::Result<()> {
+    if from == to {
+        Ok(())
+    } else {
+        Err(ErrorKind::Unsupported.into())
+    }
+}
+
+/// Extends a held lock from the byte range `from` to the byte range `to`
+/// using the locks of a [`Backend`].
+///
+/// Windows can only unlock a region exactly as it was locked, so this fails
+/// with an `Error` of kind `ErrorKind::Unsupported` unless the ranges are
+/// equal.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_extend(
+    _f: &File,
+    _backend: Backend,
+    _id: u64,
+    _lock: Lock,
+    from: Range<u64>,
+    to: Range<u64>,
+    _wait: bool,
+) -> io::Result<()> {
+    if from == to {
+        Ok(())
+    } else {
+        Err(ErrorKind::Unsupported.into())
+    }
+}
+
+/// Merges the held lock with the id `other` into the held lock with the id
+/// `id`, on adjacent byte ranges, using the locks of a [`Backend`].
+///
+/// Windows can only unlock a region exactly as it was locked, so this always
+/// fails with an `Error` of kind `ErrorKind::Unsupported`.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_merge(
+    _f: &File,
+    _backend: Backend,
+    _id: u64,
+    _other: u64,
+) -> io::Result<()> {
+    Err(ErrorKind::Unsupported.into())
+}
+
+/// Splits the held lock with the id `id` into two locks at the offset `at`
+/// using the locks of a [`Backend`], giving the second lock the id `tail`.
+///
+/// Windows can only unlock a region exactly as it was locked, so this always
+/// fails with an `Error` of kind `ErrorKind::Unsupported`.
+///
+/// [`Backend`]: ../../enum.Backend.html
+pub(crate) unsafe fn raw_backend_split(
+    _f: &File,
+    _backend: Backend,
+    _id: u64,
+    _at: u64,
+    _tail: u64,
+) -> io::Result<()> {
+    Err(ErrorKind::Unsupported.into())
+}
+
 /// Windows-specific extensions to [`FileGuard`].
 ///
 /// [`FileGuard`]: ../../struct.FileGuard.html
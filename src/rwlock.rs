@@ -0,0 +1,308 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+use std::sync::{Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError};
+
+use crate::os::raw_backend_lock;
+use crate::{next_lock_id, unlock, Backend, Lock};
+
+/// A reader-writer lock that excludes both the threads of this process and
+/// other processes.
+///
+/// Because the locks of a [`FileGuard`] may be owned by the process, they do
+/// not always exclude threads of the same process. A `ProcessRwLock` pairs an
+/// in-memory reader-writer lock with a byte range lock of a file, so a single
+/// call to [`read()`] or [`write()`] claims both. The [`Shared`] file lock is
+/// reference counted between the readers of the process, so it is only taken
+/// by the first reader and released by the last.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::ProcessRwLock;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// let lock = ProcessRwLock::new(&file, 0, 1);
+/// {
+///     let a = lock.read()?;
+///     let b = lock.read()?;
+///     // both readers share a single file lock
+/// }
+/// let w = lock.write()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`FileGuard`]: struct.FileGuard.html
+/// [`read()`]: struct.ProcessRwLock.html#method.read
+/// [`write()`]: struct.ProcessRwLock.html#method.write
+/// [`Shared`]: enum.Lock.html#variant.Shared
+pub struct ProcessRwLock<T: Deref<Target = File>> {
+    rwlock: RwLock<()>,
+    readers: Mutex<usize>,
+    file: T,
+    offset: u64,
+    len: u64,
+    backend: Backend,
+    id: u64,
+}
+
+impl<T> ProcessRwLock<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Creates a new lock over a byte range of a file.
+    ///
+    /// The byte range does not need to exist in the underlying file. No lock
+    /// is claimed until the first call to [`read()`] or [`write()`].
+    ///
+    /// [`read()`]: struct.ProcessRwLock.html#method.read
+    /// [`write()`]: struct.ProcessRwLock.html#method.write
+    pub fn new(file: T, offset: u64, len: u64) -> Self {
+        Self::with_backend(Backend::Default, file, offset, len)
+    }
+
+    /// Creates a new lock over a byte range of a file using the locks of a
+    /// [`Backend`].
+    ///
+    /// [`Backend`]: enum.Backend.html
+    pub fn with_backend(backend: Backend, file: T, offset: u64, len: u64) -> Self {
+        ProcessRwLock {
+            rwlock: RwLock::new(()),
+            readers: Mutex::new(0),
+            file,
+            offset,
+            len,
+            backend,
+            id: next_lock_id(),
+        }
+    }
+
+    /// Waits for and claims a shared lock.
+    ///
+    /// Other readers of the process may hold the lock concurrently. The file
+    /// lock is claimed by the first reader of the process, blocking until
+    /// no other process holds an [`Exclusive`] lock.
+    ///
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    pub fn read(&self) -> io::Result<ProcessReadGuard<'_, T>> {
+        let guard = self.rwlock.read().unwrap_or_else(|e| e.into_inner());
+        self.claim_read(guard, true)
+    }
+
+    /// Attempts to claim a shared lock.
+    ///
+    /// If the lock cannot be obtained without blocking, an `Error` of kind
+    /// `ErrorKind::WouldBlock` is returned.
+    pub fn try_read(&self) -> io::Result<ProcessReadGuard<'_, T>> {
+        let guard = match self.rwlock.try_read() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Err(ErrorKind::WouldBlock.into()),
+        };
+        self.claim_read(guard, false)
+    }
+
+    /// Waits for and claims an exclusive lock.
+    ///
+    /// This blocks until no other thread of the process holds the lock, and
+    /// then until no other process holds a lock on the byte range.
+    pub fn write(&self) -> io::Result<ProcessWriteGuard<'_, T>> {
+        let guard = self.rwlock.write().unwrap_or_else(|e| e.into_inner());
+        self.claim_write(guard, true)
+    }
+
+    /// Attempts to claim an exclusive lock.
+    ///
+    /// If the lock cannot be obtained without blocking, an `Error` of kind
+    /// `ErrorKind::WouldBlock` is returned.
+    pub fn try_write(&self) -> io::Result<ProcessWriteGuard<'_, T>> {
+        let guard = match self.rwlock.try_write() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return Err(ErrorKind::WouldBlock.into()),
+        };
+        self.claim_write(guard, false)
+    }
+
+    /// Gets a reference to the underlying file.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.file
+    }
+
+    /// Consumes the lock, returning the underlying file.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.file
+    }
+
+    fn claim_read<'a>(
+        &'a self,
+        guard: RwLockReadGuard<'a, ()>,
+        wait: bool,
+    ) -> io::Result<ProcessReadGuard<'a, T>> {
+        // The first reader holds the count while it waits for the file lock.
+        let mut readers = match wait {
+            true => self.readers.lock().unwrap_or_else(|e| e.into_inner()),
+            false => match self.readers.try_lock() {
+                Ok(readers) => readers,
+                Err(TryLockError::Poisoned(e)) => e.into_inner(),
+                Err(TryLockError::WouldBlock) => return Err(ErrorKind::WouldBlock.into()),
+            },
+        };
+        if *readers == 0 {
+            self.raw(Some(Lock::Shared), wait)?;
+        }
+        *readers += 1;
+        Ok(ProcessReadGuard {
+            lock: self,
+            _guard: guard,
+        })
+    }
+
+    fn release_read(&self) {
+        let mut readers = self.readers.lock().unwrap_or_else(|e| e.into_inner());
+        *readers -= 1;
+        if *readers == 0 {
+            self.release(Lock::Shared);
+        }
+    }
+
+    fn claim_write<'a>(
+        &'a self,
+        guard: RwLockWriteGuard<'a, ()>,
+        wait: bool,
+    ) -> io::Result<ProcessWriteGuard<'a, T>> {
+        self.raw(Some(Lock::Exclusive), wait)?;
+        Ok(ProcessWriteGuard {
+            lock: self,
+            _guard: guard,
+        })
+    }
+
+    /// Releases the file lock, reporting any error to the unlock error hook.
+    fn release(&self, lock: Lock) {
+        if let Err(e) = self.raw(None, false) {
+            let end = self.offset.saturating_add(self.len);
+            unlock::report(&e, lock, self.offset..end);
+        }
+    }
+
+    fn raw(&self, lock: Option<Lock>, wait: bool) -> io::Result<()> {
+        if self.len == 0 {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        let (id, off, len) = (self.id, self.offset, self.len);
+        unsafe { raw_backend_lock(&self.file, self.backend, id, lock, off, len, wait) }
+    }
+}
+
+impl<T> fmt::Debug for ProcessRwLock<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ProcessRwLock({}, {})", self.offset, self.len)
+    }
+}
+
+/// An RAII guard for the shared lock of a [`ProcessRwLock`].
+///
+/// This structure is created by the [`read()`] and [`try_read()`] methods.
+///
+/// [`ProcessRwLock`]: struct.ProcessRwLock.html
+/// [`read()`]: struct.ProcessRwLock.html#method.read
+/// [`try_read()`]: struct.ProcessRwLock.html#method.try_read
+#[must_use = "if unused the lock will immediately unlock"]
+pub struct ProcessReadGuard<'a, T: Deref<Target = File>> {
+    lock: &'a ProcessRwLock<T>,
+    _guard: RwLockReadGuard<'a, ()>,
+}
+
+impl<T> Deref for ProcessReadGuard<'_, T>
+where
+    T: Deref<Target = File>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.lock.file
+    }
+}
+
+impl<T> fmt::Debug for ProcessReadGuard<'_, T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ProcessReadGuard({}, {})",
+            self.lock.offset, self.lock.len
+        )
+    }
+}
+
+impl<T> Drop for ProcessReadGuard<'_, T>
+where
+    T: Deref<Target = File>,
+{
+    fn drop(&mut self) {
+        self.lock.release_read();
+    }
+}
+
+/// An RAII guard for the exclusive lock of a [`ProcessRwLock`].
+///
+/// This structure is created by the [`write()`] and [`try_write()`] methods.
+///
+/// [`ProcessRwLock`]: struct.ProcessRwLock.html
+/// [`write()`]: struct.ProcessRwLock.html#method.write
+/// [`try_write()`]: struct.ProcessRwLock.html#method.try_write
+#[must_use = "if unused the lock will immediately unlock"]
+pub struct ProcessWriteGuard<'a, T: Deref<Target = File>> {
+    lock: &'a ProcessRwLock<T>,
+    _guard: RwLockWriteGuard<'a, ()>,
+}
+
+impl<T> Deref for ProcessWriteGuard<'_, T>
+where
+    T: Deref<Target = File>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.lock.file
+    }
+}
+
+impl<T> fmt::Debug for ProcessWriteGuard<'_, T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "ProcessWriteGuard({}, {})",
+            self.lock.offset, self.lock.len
+        )
+    }
+}
+
+impl<T> Drop for ProcessWriteGuard<'_, T>
+where
+    T: Deref<Target = File>,
+{
+    fn drop(&mut self) {
+        self.lock.release(Lock::Exclusive);
+    }
+}
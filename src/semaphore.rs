@@ -0,0 +1,206 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+use std::time::{Duration, Instant};
+
+use crate::{Backend, FileGuard, Lock, Wait};
+
+/// A counting semaphore shared between processes.
+///
+/// Each of the `permits` is a single byte of the file, starting at `offset`,
+/// and a permit is held with an [`Exclusive`] lock of its byte. Acquiring a
+/// permit scans the bytes in order for one that may be locked without
+/// blocking. As with any lock, the permits of a process are released by the
+/// operating system if the process exits without dropping them.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::FileSemaphore;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-semaphore")?;
+///
+/// let sem = FileSemaphore::new(&file, 0, 4);
+/// let permit = sem.acquire()?;
+/// println!("holding permit {}", permit.index());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+pub struct FileSemaphore<T: Deref<Target = File>> {
+    file: T,
+    offset: u64,
+    permits: u64,
+    backend: Backend,
+}
+
+impl<T> FileSemaphore<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Creates a new semaphore of `permits` bytes of a file, starting at
+    /// `offset`.
+    ///
+    /// The bytes do not need to exist in the underlying file. No lock is
+    /// claimed until a permit is acquired.
+    pub fn new(file: T, offset: u64, permits: u64) -> Self {
+        Self::with_backend(Backend::Default, file, offset, permits)
+    }
+
+    /// Creates a new semaphore using the locks of a [`Backend`].
+    ///
+    /// [`Backend`]: enum.Backend.html
+    pub fn with_backend(backend: Backend, file: T, offset: u64, permits: u64) -> Self {
+        FileSemaphore {
+            file,
+            offset,
+            permits,
+            backend,
+        }
+    }
+
+    /// Gets the number of permits of the semaphore.
+    #[inline]
+    pub fn permits(&self) -> u64 {
+        self.permits
+    }
+
+    /// Waits for and acquires a permit.
+    ///
+    /// The permits are polled without blocking, sleeping for an exponentially
+    /// increasing delay whenever every permit is held.
+    pub fn acquire(&self) -> io::Result<Permit<'_>> {
+        self.acquire_with(Wait::Block)
+    }
+
+    /// Attempts to acquire a permit.
+    ///
+    /// If every permit is held, an `Error` of kind `ErrorKind::WouldBlock` is
+    /// returned.
+    pub fn try_acquire(&self) -> io::Result<Permit<'_>> {
+        self.acquire_with(Wait::Try)
+    }
+
+    /// Waits up to `timeout` to acquire a permit.
+    ///
+    /// If no permit is released before the timeout elapses, an `Error` of
+    /// kind `ErrorKind::TimedOut` is returned. A timeout too large to ever
+    /// elapse waits until a permit is available.
+    pub fn acquire_timeout(&self, timeout: Duration) -> io::Result<Permit<'_>> {
+        self.acquire_with(Wait::Timeout(timeout))
+    }
+
+    /// Waits until `deadline` to acquire a permit.
+    ///
+    /// If no permit is released before the deadline passes, an `Error` of
+    /// kind `ErrorKind::TimedOut` is returned.
+    pub fn acquire_deadline(&self, deadline: Instant) -> io::Result<Permit<'_>> {
+        self.acquire_with(Wait::Deadline(deadline))
+    }
+
+    /// Gets a reference to the underlying file.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        &self.file
+    }
+
+    /// Consumes the semaphore, returning the underlying file.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.file
+    }
+
+    fn acquire_with(&self, wait: Wait) -> io::Result<Permit<'_>> {
+        if self.permits == 0 || self.offset.checked_add(self.permits).is_none() {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        let mut polling = wait.polling();
+        loop {
+            if let Some(permit) = self.scan()? {
+                return Ok(permit);
+            }
+            match polling {
+                None => return Err(ErrorKind::WouldBlock.into()),
+                Some((ref mut backoff, deadline)) => {
+                    if !backoff.sleep_until(deadline) {
+                        return Err(ErrorKind::TimedOut.into());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Makes a single pass over the permits, claiming the first that is
+    /// free.
+    fn scan(&self) -> io::Result<Option<Permit<'_>>> {
+        for index in 0..self.permits {
+            match self
+                .backend
+                .try_lock(&*self.file, Lock::Exclusive, self.offset + index, 1)
+            {
+                Ok(guard) => return Ok(Some(Permit { guard, index })),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<T> fmt::Debug for FileSemaphore<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "FileSemaphore({}, {})", self.offset, self.permits)
+    }
+}
+
+/// An RAII guard for a permit of a [`FileSemaphore`]. When dropped, the
+/// permit is released.
+///
+/// This structure is created by the [`acquire()`] method and its variants.
+///
+/// [`FileSemaphore`]: struct.FileSemaphore.html
+/// [`acquire()`]: struct.FileSemaphore.html#method.acquire
+#[must_use = "if unused the permit will immediately be released"]
+pub struct Permit<'a> {
+    guard: FileGuard<&'a File>,
+    index: u64,
+}
+
+impl<'a> Permit<'a> {
+    /// Gets the index of the permit, from zero up to the number of permits
+    /// of the semaphore.
+    #[inline]
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Gets the guard holding the byte of the permit.
+    #[inline]
+    pub fn guard(&self) -> &FileGuard<&'a File> {
+        &self.guard
+    }
+
+    /// Consumes the permit, returning the guard holding its byte.
+    #[inline]
+    pub fn into_guard(self) -> FileGuard<&'a File> {
+        self.guard
+    }
+}
+
+impl fmt::Debug for Permit<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Permit({})", self.index)
+    }
+}
@@ -0,0 +1,140 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::{Deref, Range};
+use std::{fmt, slice, vec};
+
+use crate::os::raw_file_id;
+use crate::{Backend, FileGuard, Lock, Wait};
+
+/// A set of guards for byte ranges of one or more files, claimed together.
+///
+/// When dropped, every lock of the set is unlocked. The guards are ordered by
+/// file and then by offset, which is the order they were claimed in,
+/// regardless of the order the ranges were given in.
+///
+/// This structure is created by the [`lock_many()`], [`try_lock_many()`],
+/// [`lock_all()`], and [`try_lock_all()`] functions.
+///
+/// [`lock_many()`]: fn.lock_many.html
+/// [`try_lock_many()`]: fn.try_lock_many.html
+/// [`lock_all()`]: fn.lock_all.html
+/// [`try_lock_all()`]: fn.try_lock_all.html
+#[must_use = "if unused the file locks will immediately unlock"]
+pub struct GuardSet<T: Deref<Target = File>> {
+    guards: Vec<FileGuard<T>>,
+}
+
+impl<T> GuardSet<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Gets the number of guards in the set.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.guards.len()
+    }
+
+    /// Checks if the set holds no guards.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.guards.is_empty()
+    }
+
+    /// Gets the guard at `index`, in order of file and offset.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&FileGuard<T>> {
+        self.guards.get(index)
+    }
+
+    /// Returns an iterator over the guards, in order of file and offset.
+    #[inline]
+    pub fn iter(&self) -> slice::Iter<'_, FileGuard<T>> {
+        self.guards.iter()
+    }
+
+    /// Consumes the set, returning the guards so they may be released
+    /// individually.
+    #[inline]
+    pub fn into_guards(self) -> Vec<FileGuard<T>> {
+        self.guards
+    }
+}
+
+impl<T> fmt::Debug for GuardSet<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(&self.guards).finish()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a GuardSet<T>
+where
+    T: Deref<Target = File>,
+{
+    type Item = &'a FileGuard<T>;
+    type IntoIter = slice::Iter<'a, FileGuard<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.guards.iter()
+    }
+}
+
+impl<T> IntoIterator for GuardSet<T>
+where
+    T: Deref<Target = File>,
+{
+    type Item = FileGuard<T>;
+    type IntoIter = vec::IntoIter<FileGuard<T>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.guards.into_iter()
+    }
+}
+
+/// Claims the locks of `requests` in order of file and offset.
+///
+/// Files are ordered by their device and inode, or volume and file index on
+/// Windows, so the same file opened more than once is ordered as one. Each
+/// range must be non-empty and must not overlap another range of the same
+/// file, and a range ending at `u64::MAX` extends to the end of the file and
+/// beyond. If any lock fails, the guards already claimed are dropped,
+/// unlocking them.
+pub(crate) fn acquire<T>(
+    backend: Backend,
+    requests: impl IntoIterator<Item = (T, Lock, Range<u64>)>,
+    wait: Wait,
+) -> io::Result<GuardSet<T>>
+where
+    T: Deref<Target = File>,
+{
+    let mut order = Vec::new();
+    for (file, lock, range) in requests {
+        if range.start >= range.end {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+        order.push((raw_file_id(&file)?, file, lock, range));
+    }
+    order.sort_by_key(|(id, _, _, range)| (*id, range.start, range.end));
+
+    for pair in order.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        if a.0 == b.0 && a.3.end > b.3.start {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+    }
+
+    let mut guards = Vec::with_capacity(order.len());
+    for (_, file, lock, range) in order {
+        let len = match range.end {
+            u64::MAX => None,
+            end => Some(end - range.start),
+        };
+        let guard = backend
+            .options(Some(lock), range.start, len, wait)
+            .acquire(file)?;
+        guards.push(guard);
+    }
+    Ok(GuardSet { guards })
+}
@@ -0,0 +1,169 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+
+use crate::{Backend, FileGuard, FileSemaphore, Lock, Permit};
+
+/// Allocates small, unique, and reusable slot numbers between processes.
+///
+/// Each of the `slots` is a single byte of the file, starting at `offset`,
+/// and a slot is claimed with an [`Exclusive`] lock of its byte. Claiming
+/// always takes the lowest free slot, so the slot numbers in use stay dense,
+/// and a slot is freed when its [`Slot`] is dropped or its process exits.
+///
+/// # Examples
+///
+/// ```
+/// use file_guard::SlotAllocator;
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-slots")?;
+///
+/// let slots = SlotAllocator::new(&file, 0, 64);
+/// let slot = slots.try_claim()?;
+/// println!("running as worker {}", slot.index());
+/// assert!(slots.claimed()?.contains(&slot.index()));
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+/// [`Slot`]: struct.Slot.html
+pub struct SlotAllocator<T: Deref<Target = File>> {
+    sem: FileSemaphore<T>,
+    offset: u64,
+    backend: Backend,
+}
+
+impl<T> SlotAllocator<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Creates a new allocator of `slots` bytes of a file, starting at
+    /// `offset`.
+    ///
+    /// The bytes do not need to exist in the underlying file.
+    pub fn new(file: T, offset: u64, slots: u64) -> Self {
+        Self::with_backend(Backend::Default, file, offset, slots)
+    }
+
+    /// Creates a new allocator using the locks of a [`Backend`].
+    ///
+    /// [`Backend`]: enum.Backend.html
+    pub fn with_backend(backend: Backend, file: T, offset: u64, slots: u64) -> Self {
+        SlotAllocator {
+            sem: FileSemaphore::with_backend(backend, file, offset, slots),
+            offset,
+            backend,
+        }
+    }
+
+    /// Gets the number of slots of the allocator.
+    #[inline]
+    pub fn slots(&self) -> u64 {
+        self.sem.permits()
+    }
+
+    /// Waits for and claims the lowest free slot.
+    ///
+    /// The slots are polled without blocking, sleeping for an exponentially
+    /// increasing delay whenever every slot is claimed.
+    pub fn claim(&self) -> io::Result<Slot<'_>> {
+        self.sem.acquire().map(|permit| Slot { permit })
+    }
+
+    /// Attempts to claim the lowest free slot.
+    ///
+    /// If every slot is claimed, an `Error` of kind `ErrorKind::WouldBlock` is
+    /// returned.
+    pub fn try_claim(&self) -> io::Result<Slot<'_>> {
+        self.sem.try_acquire().map(|permit| Slot { permit })
+    }
+
+    /// Lists the slots that are currently claimed, in increasing order.
+    ///
+    /// Each slot is checked with [`probe()`], which with the [`Default`]
+    /// backend includes the slots claimed by this process. No lock is claimed
+    /// on Unix systems, but on Windows each free slot is briefly claimed and
+    /// released. The result is only a snapshot and may be stale by the time
+    /// it is used.
+    ///
+    /// [`probe()`]: fn.probe.html
+    /// [`Default`]: enum.Backend.html#variant.Default
+    pub fn claimed(&self) -> io::Result<Vec<u64>> {
+        if self.offset.checked_add(self.slots()).is_none() {
+            return Err(ErrorKind::InvalidInput.into());
+        }
+
+        let file = &**self.sem.get_ref();
+        let mut claimed = Vec::new();
+        for index in 0..self.slots() {
+            let off = self.offset + index;
+            if self.backend.probe(file, Lock::Exclusive, off, 1)?.is_some() {
+                claimed.push(index);
+            }
+        }
+        Ok(claimed)
+    }
+
+    /// Gets a reference to the underlying file.
+    #[inline]
+    pub fn get_ref(&self) -> &T {
+        self.sem.get_ref()
+    }
+
+    /// Consumes the allocator, returning the underlying file.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.sem.into_inner()
+    }
+}
+
+impl<T> fmt::Debug for SlotAllocator<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SlotAllocator({}, {})", self.offset, self.slots())
+    }
+}
+
+/// An RAII guard for a slot claimed from a [`SlotAllocator`]. When dropped,
+/// the slot is freed.
+///
+/// This structure is created by the [`claim()`] and [`try_claim()`] methods.
+///
+/// [`SlotAllocator`]: struct.SlotAllocator.html
+/// [`claim()`]: struct.SlotAllocator.html#method.claim
+/// [`try_claim()`]: struct.SlotAllocator.html#method.try_claim
+#[must_use = "if unused the slot will immediately be freed"]
+pub struct Slot<'a> {
+    permit: Permit<'a>,
+}
+
+impl<'a> Slot<'a> {
+    /// Gets the index of the slot, from zero up to the number of slots of
+    /// the allocator.
+    #[inline]
+    pub fn index(&self) -> u64 {
+        self.permit.index()
+    }
+
+    /// Gets the guard holding the byte of the slot.
+    #[inline]
+    pub fn guard(&self) -> &FileGuard<&'a File> {
+        self.permit.guard()
+    }
+}
+
+impl fmt::Debug for Slot<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Slot({})", self.index())
+    }
+}
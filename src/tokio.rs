@@ -0,0 +1,106 @@
+//! Provides asynchronous lock acquisition for the Tokio runtime.
+//!
+//! Waiting for a lock with [`lock()`](../fn.lock.html) blocks the calling
+//! thread, which stalls a Tokio worker. The functions in this module instead
+//! poll for the lock without blocking, sleeping on the Tokio timer between
+//! attempts.
+//!
+//! The returned futures are cancellation-safe: a lock is only ever held once
+//! the future completes with a [`FileGuard`], so dropping the future before
+//! then never leaves a lock behind.
+//!
+//! This module requires the `tokio` feature.
+//!
+//! # Examples
+//!
+//! ```
+//! use file_guard::Lock;
+//! use std::fs::OpenOptions;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> std::io::Result<()> {
+//! let file = OpenOptions::new()
+//!     .read(true)
+//!     .write(true)
+//!     .create(true)
+//!     .open("example-lock")?;
+//!
+//! let lock = file_guard::tokio::lock(&file, Lock::Exclusive, 0, 1).await?;
+//! // the lock will be unlocked when it goes out of scope
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`FileGuard`]: ../struct.FileGuard.html
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::ops::Deref;
+
+use crate::{next_lock_id, Backend, FileGuard, Lock, LockOptions, Wait};
+
+/// Wait and claim the desired [`Lock`] type using a byte range of a file.
+///
+/// The byte range does not need to exist in the underlying file.
+///
+/// [`Lock`]: ../enum.Lock.html
+pub async fn lock<T: Deref<Target = File>>(
+    file: T,
+    lock: Lock,
+    offset: u64,
+    len: u64,
+) -> io::Result<FileGuard<T>> {
+    let options = Backend::Default.options(Some(lock), offset, len, Wait::Block);
+    acquire(&options, file).await
+}
+
+/// Wait and claim either an [`Exclusive`] or [`Shared`] lock for a byte range
+/// of a file.
+///
+/// When successful, the [`FileGuard`] may be inspected for the lock type
+/// obtained using [`.lock_type()`].
+///
+/// The byte range does not need to exist in the underlying file.
+///
+/// [`Exclusive`]: ../enum.Lock.html#variant.Exclusive
+/// [`Shared`]: ../enum.Lock.html#variant.Shared
+/// [`FileGuard`]: ../struct.FileGuard.html
+/// [`.lock_type()`]: ../struct.FileGuard.html#method.lock_type
+pub async fn lock_any<T: Deref<Target = File>>(
+    file: T,
+    offset: u64,
+    len: u64,
+) -> io::Result<FileGuard<T>> {
+    let options = Backend::Default.options(None, offset, len, Wait::Block);
+    acquire(&options, file).await
+}
+
+/// Claim a lock on `file` with the options specified by `options`.
+///
+/// The [`Wait`] strategy is respected, except that [`Wait::Block`] polls for
+/// the lock rather than blocking the thread.
+///
+/// [`Wait`]: ../enum.Wait.html
+/// [`Wait::Block`]: ../enum.Wait.html#variant.Block
+pub async fn acquire<T: Deref<Target = File>>(
+    options: &LockOptions,
+    file: T,
+) -> io::Result<FileGuard<T>> {
+    let id = next_lock_id();
+    let mut polling = options.polling();
+    loop {
+        match options.attempt(&file, id) {
+            Ok(lock) => return Ok(options.guard(file, lock, id)),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => {
+                let (backoff, deadline) = match polling {
+                    Some((ref mut backoff, deadline)) => (backoff, deadline),
+                    None => return Err(e),
+                };
+                match backoff.delay_until(deadline) {
+                    Some(delay) => ::tokio::time::sleep(delay).await,
+                    None => return Err(ErrorKind::TimedOut.into()),
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
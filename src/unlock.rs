@@ -0,0 +1,58 @@
+use std::io;
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+
+use crate::Lock;
+
+/// A hook registered with [`set_unlock_error_hook()`].
+///
+/// [`set_unlock_error_hook()`]: fn.set_unlock_error_hook.html
+pub type UnlockErrorHook = Box<dyn Fn(&io::Error, Lock, Range<u64>) + Send + Sync + 'static>;
+
+type Hook = Arc<dyn Fn(&io::Error, Lock, Range<u64>) + Send + Sync + 'static>;
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+
+/// Registers a custom hook for errors that occur while a [`FileGuard`] is
+/// implicitly unlocked by being dropped, replacing any previously registered
+/// hook.
+///
+/// The hook is given the error along with the [`Lock`] type and byte range
+/// that failed to unlock. By default these errors are ignored. Use
+/// [`.unlock()`] to handle the error of a specific guard instead.
+///
+/// # Examples
+///
+/// ```
+/// file_guard::set_unlock_error_hook(Box::new(|err, lock, range| {
+///     eprintln!("failed to unlock {} {:?}: {}", lock, range, err);
+/// }));
+/// ```
+///
+/// [`FileGuard`]: struct.FileGuard.html
+/// [`Lock`]: enum.Lock.html
+/// [`.unlock()`]: struct.FileGuard.html#method.unlock
+pub fn set_unlock_error_hook(hook: UnlockErrorHook) {
+    *HOOK.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::from(hook));
+}
+
+/// Unregisters the current unlock error hook, returning it.
+///
+/// See [`set_unlock_error_hook()`] for details.
+///
+/// [`set_unlock_error_hook()`]: fn.set_unlock_error_hook.html
+pub fn take_unlock_error_hook() -> Option<UnlockErrorHook> {
+    let hook = HOOK.write().unwrap_or_else(|e| e.into_inner()).take()?;
+    Some(Box::new(move |err, lock, range| hook(err, lock, range)))
+}
+
+/// Reports an error from unlocking a dropped guard to the registered hook.
+///
+/// The hook is called without holding the registration, so it may replace
+/// itself.
+pub(crate) fn report(err: &io::Error, lock: Lock, range: Range<u64>) {
+    let hook = HOOK.read().unwrap_or_else(|e| e.into_inner()).clone();
+    if let Some(hook) = hook {
+        hook(err, lock, range);
+    }
+}
@@ -0,0 +1,234 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::ops::{Deref, Range};
+
+use crate::os::raw_backend_lock;
+use crate::{fair, next_lock_id, unlock, Backend, FileGuard, Lock, Wait};
+
+/// A [`Shared`] lock of a byte range that may be safely upgraded to an
+/// [`Exclusive`] lock on every platform.
+///
+/// Upgrading a lock in place deadlocks when two holders of a [`Shared`] lock
+/// both try to upgrade, as each waits for the other to release its lock. In
+/// the same way as the RESERVED lock of SQLite, an `UpgradableGuard` also
+/// holds an [`Exclusive`] lock on an intent byte that immediately follows the
+/// byte range. Only one guard may hold the intent byte at a time, so at most
+/// one reader may upgrade, while plain [`Shared`] locks of the byte range may
+/// still be claimed until the upgrade begins. While upgrading, the guard also
+/// holds the pending byte that follows the intent byte, in the same way as
+/// the PENDING lock of SQLite, so that the readers using [`lock_fair()`] are
+/// held back until the upgrade completes.
+///
+/// The protocol only holds when every writer of the byte range either
+/// upgrades an `UpgradableGuard` or uses [`lock_fair()`], rather than claiming
+/// an [`Exclusive`] lock directly. The upgrade releases the [`Shared`] lock
+/// before claiming the [`Exclusive`] one, as Windows locks cannot be
+/// converted, but the intent and pending bytes ensure that no other writer
+/// may claim the byte range in between.
+///
+/// This structure is created by the [`lock_upgradable()`] and
+/// [`try_lock_upgradable()`] functions.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::OpenOptions;
+///
+/// # fn main() -> std::io::Result<()> {
+/// let file = OpenOptions::new()
+///     .read(true)
+///     .write(true)
+///     .create(true)
+///     .open("example-lock")?;
+///
+/// let mut lock = file_guard::lock_upgradable(&file, 0, 64)?;
+/// // read the byte range, deciding whether to write
+/// lock.upgrade()?;
+/// assert!(lock.is_exclusive());
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Shared`]: enum.Lock.html#variant.Shared
+/// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+/// [`lock_fair()`]: fn.lock_fair.html
+/// [`lock_upgradable()`]: fn.lock_upgradable.html
+/// [`try_lock_upgradable()`]: fn.try_lock_upgradable.html
+#[must_use = "if unused the file lock will immediately unlock"]
+pub struct UpgradableGuard<T: Deref<Target = File>> {
+    guard: FileGuard<T>,
+    intent: u64,
+}
+
+impl<T> UpgradableGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    /// Gets the [`Lock`] type currently held on the byte range.
+    ///
+    /// [`Lock`]: enum.Lock.html
+    #[inline]
+    pub fn lock_type(&self) -> Lock {
+        self.guard.lock
+    }
+
+    /// Test if the byte range is currently held [`Exclusive`].
+    ///
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    #[inline]
+    pub fn is_exclusive(&self) -> bool {
+        self.guard.is_exclusive()
+    }
+
+    /// Gets the byte range of the held lock, not including the intent byte.
+    #[inline]
+    pub fn range(&self) -> Range<u64> {
+        self.guard.range()
+    }
+
+    /// Gets the offset of the intent byte.
+    #[inline]
+    pub fn intent_offset(&self) -> u64 {
+        self.guard.range().end
+    }
+
+    /// Waits for the other [`Shared`] locks of the byte range to be released
+    /// and claims an [`Exclusive`] lock.
+    ///
+    /// New readers using [`lock_fair()`] wait until the upgrade completes,
+    /// so the upgrade cannot be starved by a steady stream of readers.
+    ///
+    /// If the lock is already [`Exclusive`], no change is made and the method
+    /// succeeds.
+    ///
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    /// [`lock_fair()`]: fn.lock_fair.html
+    pub fn upgrade(&mut self) -> io::Result<()> {
+        self.raw_upgrade(true)
+    }
+
+    /// Attempts to claim an [`Exclusive`] lock of the byte range.
+    ///
+    /// As with [`upgrade()`], the [`Shared`] lock is released before the
+    /// [`Exclusive`] lock is attempted. If other [`Shared`] locks of the byte
+    /// range are held, the [`Shared`] lock is claimed again, and an `Error` of
+    /// kind `ErrorKind::WouldBlock` is returned. Claiming it again waits for
+    /// any writer that locked the byte range in between, so this may block
+    /// when a writer claims an [`Exclusive`] lock directly. Writers that follow
+    /// the protocol are held back by the intent byte, so they cannot.
+    ///
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    /// [`upgrade()`]: struct.UpgradableGuard.html#method.upgrade
+    pub fn try_upgrade(&mut self) -> io::Result<()> {
+        self.raw_upgrade(false)
+    }
+
+    /// Exchanges the [`Exclusive`] lock of the byte range for a [`Shared`]
+    /// one, keeping the intent byte so the guard may upgrade again.
+    ///
+    /// [`Shared`]: enum.Lock.html#variant.Shared
+    /// [`Exclusive`]: enum.Lock.html#variant.Exclusive
+    pub fn downgrade(&mut self) -> io::Result<()> {
+        self.guard.downgrade()
+    }
+
+    fn raw_upgrade(&mut self, wait: bool) -> io::Result<()> {
+        if self.guard.is_exclusive() {
+            return Ok(());
+        }
+
+        let g = &mut self.guard;
+        unsafe {
+            fair::raw_upgrade(&g.file, g.backend, g.id, g.offset, g.raw_len(), wait)?;
+        }
+        g.lock = Lock::Exclusive;
+        Ok(())
+    }
+
+    unsafe fn raw_unlock_intent(&self) -> io::Result<()> {
+        let g = &self.guard;
+        let off = self.intent_offset();
+        raw_backend_lock(&g.file, g.backend, self.intent, None, off, 1, false)
+    }
+}
+
+impl<T> fmt::Debug for UpgradableGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = self.guard.range();
+        write!(
+            f,
+            "UpgradableGuard::{:?}({}, {})",
+            self.guard.lock,
+            range.start,
+            range.end - range.start
+        )
+    }
+}
+
+impl<T> Deref for UpgradableGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard.file
+    }
+}
+
+impl<T> Drop for UpgradableGuard<T>
+where
+    T: Deref<Target = File>,
+{
+    fn drop(&mut self) {
+        if let Err(e) = unsafe { self.raw_unlock_intent() } {
+            let off = self.intent_offset();
+            unlock::report(&e, Lock::Exclusive, off..off + 1);
+        }
+    }
+}
+
+/// Claims the intent byte following a byte range and then a fair [`Shared`]
+/// lock of the byte range, releasing the intent byte if the second lock
+/// fails.
+///
+/// [`Shared`]: enum.Lock.html#variant.Shared
+pub(crate) fn acquire<T>(
+    backend: Backend,
+    file: T,
+    offset: u64,
+    len: u64,
+    wait: bool,
+) -> io::Result<UpgradableGuard<T>>
+where
+    T: Deref<Target = File>,
+{
+    // The pending byte follows the intent byte, so this validates both.
+    let (intent, intent_id) = (fair::pending_offset(offset, len)? - 1, next_lock_id());
+
+    let exclusive = Some(Lock::Exclusive);
+    unsafe {
+        raw_backend_lock(&file, backend, intent_id, exclusive, intent, 1, wait)?;
+    }
+    // The lock is handed over to a guard that owns the file.
+    match fair::acquire(backend, &*file, Lock::Shared, offset, len, wait).map(FileGuard::into_id) {
+        Ok(id) => Ok(UpgradableGuard {
+            guard: backend
+                .options(Some(Lock::Shared), offset, len, Wait::Block)
+                .guard(file, Lock::Shared, id),
+            intent: intent_id,
+        }),
+        Err(e) => {
+            unsafe {
+                let _ = raw_backend_lock(&file, backend, intent_id, None, intent, 1, false);
+            }
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::io::{self, ErrorKind};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+use std::{env, thread};
+
+use file_guard::{FileBarrier, Lock};
+
+mod common;
+
+use common::open;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn test_barrier() -> io::Result<()> {
+    let path = "test-barrier";
+    open(path)?.set_len(0)?;
+
+    let threads: Vec<_> = (0..3)
+        .map(|_| {
+            thread::spawn(move || -> io::Result<Vec<bool>> {
+                let f = open(path)?;
+                let barrier = FileBarrier::new(&f, 4);
+                // the barrier is reused for each round
+                (0..3).map(|_| barrier.wait_timeout(3, TIMEOUT)).collect()
+            })
+        })
+        .collect();
+
+    let mut leaders = [0; 3];
+    for t in threads {
+        for (round, leader) in t.join().unwrap()?.into_iter().enumerate() {
+            leaders[round] += leader as u32;
+        }
+    }
+    assert_eq!(leaders, [1, 1, 1]);
+
+    Ok(())
+}
+
+#[test]
+fn test_barrier_timeout() -> io::Result<()> {
+    let path = "test-barrier-timeout";
+    let f = open(path)?;
+    f.set_len(0)?;
+
+    let barrier = FileBarrier::new(&f, 0);
+    let e = barrier
+        .wait_timeout(2, Duration::from_millis(50))
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::TimedOut);
+
+    // the timed out process is no longer counted
+    let other = thread::spawn(move || -> io::Result<bool> {
+        let f = open(path)?;
+        FileBarrier::new(&f, 0).wait_timeout(2, TIMEOUT)
+    });
+    let leader = barrier.wait_timeout(2, TIMEOUT)?;
+    assert_ne!(leader, other.join().unwrap()?);
+
+    // a timeout too large for a deadline waits without one
+    assert!(barrier.wait_timeout(1, Duration::MAX)?);
+
+    let e = barrier.wait(0).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+#[test]
+fn test_barrier_exit() -> io::Result<()> {
+    let path = "test-barrier-exit";
+    if env::var_os("TEST_BARRIER_EXIT").is_some() {
+        // the child waits until it is killed
+        FileBarrier::new(&open(path)?, 0).wait(2)?;
+        unreachable!("barrier released without the parent");
+    }
+
+    let f = open(path)?;
+    f.set_len(0)?;
+    let mut child = Command::new(env::current_exe()?)
+        .args(["--exact", "test_barrier_exit"])
+        .env("TEST_BARRIER_EXIT", "1")
+        .stdout(Stdio::null())
+        .spawn()?;
+
+    // the child holds an arrival slot once it is waiting
+    let deadline = Instant::now() + TIMEOUT;
+    while file_guard::probe(&f, Lock::Exclusive, 8, 2)?.is_none() {
+        assert!(Instant::now() < deadline, "child never arrived");
+        thread::sleep(Duration::from_millis(10));
+    }
+    child.kill()?;
+    child.wait()?;
+
+    // the exited child is no longer counted
+    let barrier = FileBarrier::new(&f, 0);
+    let e = barrier
+        .wait_timeout(2, Duration::from_millis(50))
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::TimedOut);
+
+    Ok(())
+}
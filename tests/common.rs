@@ -0,0 +1,22 @@
+use std::fs::{File, OpenOptions};
+use std::io;
+
+use file_guard::{Backend, Lock};
+
+pub fn open(path: &str) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)
+}
+
+// Open file description locks conflict with the process's own fcntl locks,
+// so they can be used to observe what the kernel holds.
+#[allow(dead_code)]
+pub fn held(f: &File, off: u64, len: u64) -> io::Result<Option<Lock>> {
+    Ok(Backend::OpenFile
+        .probe(f, Lock::Exclusive, off, len)?
+        .map(|c| c.lock))
+}
@@ -0,0 +1,110 @@
+#![cfg(target_os = "linux")]
+
+use std::io::{self, ErrorKind};
+use std::rc::Rc;
+
+use file_guard::{Backend, FileGuard, Lock, LockOptions};
+
+mod common;
+
+use common::{held, open};
+
+#[test]
+fn test_extend() -> io::Result<()> {
+    let path = "test-extend";
+    let f = open(path)?;
+    let observer = open(path)?;
+
+    let mut g = file_guard::lock(&f, Lock::Exclusive, 16, 16)?;
+    g.extend_to(8..48)?;
+    assert_eq!(g.range(), 8..48);
+    assert_eq!(held(&observer, 8, 40)?, Some(Lock::Exclusive));
+    assert_eq!(held(&observer, 0, 8)?, None);
+
+    let e = g.extend_to(16..64).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let other = file_guard::lock(&f, Lock::Shared, 60, 4)?;
+    let e = g.try_extend_to(8..64).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    assert_eq!(g.range(), 8..48);
+    drop(other);
+
+    let ofd = open(path)?;
+    let other = Backend::OpenFile.lock(&ofd, Lock::Shared, 60, 4)?;
+    let e = g.try_extend_to(8..64).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    assert_eq!(g.range(), 8..48);
+    assert_eq!(held(&observer, 48, 12)?, None);
+    drop(other);
+
+    g.try_extend_to(8..)?;
+    assert!(g.is_unbounded());
+    assert_eq!(held(&observer, 1 << 40, 1)?, Some(Lock::Exclusive));
+    drop(g);
+    assert_eq!(held(&observer, 0, 1 << 40)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_extend_to_max() -> io::Result<()> {
+    let path = "test-extend-max";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    // a range ending at u64::MAX is locked to the end of the file
+    let mut g = Backend::OpenFile.lock(&f, Lock::Exclusive, 0, 8)?;
+    g.extend_to(0..u64::MAX)?;
+    assert!(g.is_unbounded());
+    g.unlock()?;
+    drop(Backend::OpenFile.try_lock(&other, Lock::Exclusive, 0, 8)?);
+
+    let mut g = file_guard::lock(&f, Lock::Exclusive, 0, 8)?;
+    g.extend_to(..=u64::MAX - 1)?;
+    assert!(g.is_unbounded());
+    g.unlock()?;
+    assert_eq!(held(&other, 0, 1 << 40)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_merge() -> io::Result<()> {
+    let path = "test-merge";
+    let f = Rc::new(open(path)?);
+    let observer = open(path)?;
+
+    let a = file_guard::lock(f.clone(), Lock::Shared, 0, 8)?;
+    let b = file_guard::lock(f.clone(), Lock::Shared, 8, 8)?;
+    let g = FileGuard::merge(b, a)?;
+    assert_eq!(g.range(), 0..16);
+    assert_eq!(Rc::strong_count(&f), 2);
+
+    // a failed merge returns both guards with their locks
+    let c = file_guard::lock(f.clone(), Lock::Shared, 32, 8)?;
+    let (e, g, c) = FileGuard::merge(g, c).unwrap_err().into_parts();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    assert_eq!((g.range(), c.range()), (0..16, 32..40));
+    assert_eq!(held(&observer, 0, 16)?, Some(Lock::Shared));
+    assert_eq!(held(&observer, 32, 8)?, Some(Lock::Shared));
+    drop((g, c));
+    assert_eq!(held(&observer, 0, 64)?, None);
+
+    let a = file_guard::lock(f.clone(), Lock::Exclusive, 0, 8)?;
+    let b = LockOptions::new()
+        .lock(Lock::Exclusive)
+        .range(8..)
+        .acquire(f.clone())?;
+    let mut g = FileGuard::merge(a, b)?;
+    assert!(g.is_unbounded());
+    assert_eq!(g.range(), 0..u64::MAX);
+
+    let (a, b) = g.split_at(4)?;
+    g = FileGuard::merge(a, b)?;
+    assert_eq!(g.range(), 0..u64::MAX);
+    drop(g);
+    assert_eq!(held(&observer, 0, 1 << 40)?, None);
+
+    Ok(())
+}
@@ -0,0 +1,100 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+use file_guard::{Lock, UpgradableGuard};
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_fair_writer() -> io::Result<()> {
+    let path = "test-fair-writer";
+    let (a, b, c) = (open(path)?, open(path)?, open(path)?);
+
+    let reader = file_guard::lock_fair(&a, Lock::Shared, 0, 8)?;
+    assert_eq!(reader.range(), 0..8);
+
+    thread::scope(|s| {
+        let writer =
+            s.spawn(|| file_guard::lock_fair(&b, Lock::Exclusive, 0, 8).map(|g| g.lock_type()));
+        thread::sleep(Duration::from_millis(20));
+
+        // plain readers are still admitted, but fair ones wait for the writer
+        drop(file_guard::try_lock(&c, Lock::Shared, 0, 8).unwrap());
+        let e = file_guard::try_lock_fair(&c, Lock::Shared, 0, 8).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+        drop(reader);
+        assert_eq!(writer.join().unwrap().unwrap(), Lock::Exclusive);
+    });
+
+    // the pending byte is released once the writer is done waiting
+    drop(file_guard::try_lock_fair(&c, Lock::Shared, 0, 8)?);
+
+    Ok(())
+}
+
+fn upgrade(mut g: UpgradableGuard<&File>) -> io::Result<Lock> {
+    g.upgrade()?;
+    Ok(g.lock_type())
+}
+
+#[test]
+fn test_fair_upgrade() -> io::Result<()> {
+    let path = "test-fair-upgrade";
+    let (a, b, c) = (open(path)?, open(path)?, open(path)?);
+
+    let g = file_guard::lock_upgradable(&a, 0, 8)?;
+    let reader = file_guard::lock_fair(&b, Lock::Shared, 0, 8)?;
+
+    thread::scope(|s| {
+        let upgraded = s.spawn(move || upgrade(g));
+        thread::sleep(Duration::from_millis(20));
+
+        let e = file_guard::try_lock_fair(&c, Lock::Shared, 0, 8).unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+        drop(reader);
+        assert_eq!(upgraded.join().unwrap().unwrap(), Lock::Exclusive);
+    });
+
+    Ok(())
+}
+
+#[test]
+fn test_fair_upgrade_writer() -> io::Result<()> {
+    let path = "test-fair-upgrade-writer";
+    let (a, b) = (open(path)?, open(path)?);
+
+    let mut g = file_guard::lock_upgradable(&a, 0, 8)?;
+
+    thread::scope(|s| -> io::Result<()> {
+        let writer =
+            s.spawn(|| file_guard::lock_fair(&b, Lock::Exclusive, 0, 8).map(|g| g.lock_type()));
+        thread::sleep(Duration::from_millis(20));
+
+        // the waiting writer holds neither the intent nor the pending byte
+        g.upgrade()?;
+        assert!(g.is_exclusive());
+
+        drop(g);
+        assert_eq!(writer.join().unwrap()?, Lock::Exclusive);
+        Ok(())
+    })
+}
+
+#[test]
+fn test_fair_invalid() -> io::Result<()> {
+    let f = open("test-fair-invalid")?;
+
+    let e = file_guard::lock_fair(&f, Lock::Shared, 0, 0).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let e = file_guard::try_lock_fair(&f, Lock::Exclusive, u64::MAX - 2, 1).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
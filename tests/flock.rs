@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+use std::io::{self, ErrorKind};
+
+use file_guard::os::unix::flock;
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_flock() -> io::Result<()> {
+    let path = "test-flock";
+    let a = open(path)?;
+    let b = open(path)?;
+
+    let c = open(path)?;
+
+    let g = flock::try_lock(&a, Lock::Shared)?;
+    let h = flock::try_lock(&b, Lock::Shared)?;
+    let (e, g) = g.try_upgrade().unwrap_err().into_parts();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    let g = g.unwrap();
+    assert!(g.is_shared());
+
+    // the shared lock is still held after the failed upgrade
+    drop(h);
+    let e = flock::try_lock(&c, Lock::Exclusive).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    drop(flock::try_lock(&c, Lock::Shared)?);
+
+    let mut g = g.try_upgrade()?;
+    assert!(g.is_exclusive());
+
+    let e = flock::try_lock(&b, Lock::Shared).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    // byte range locks do not conflict with flock
+    let r = file_guard::try_lock(&b, Lock::Exclusive, 0, 1)?;
+    drop(r);
+
+    g.downgrade()?;
+    assert!(g.is_shared());
+    let h = flock::try_lock(&b, Lock::Shared)?;
+    assert!(h.is_shared());
+
+    Ok(())
+}
@@ -0,0 +1,107 @@
+#![cfg(feature = "future")]
+
+use std::future::Future;
+use std::io;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake};
+use std::thread::{self, Thread};
+
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let waker = Arc::new(Unpark(thread::current())).into();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(v) => return v,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+#[test]
+fn test_future_lock() -> io::Result<()> {
+    let f = open("test-future-lock")?;
+
+    let g = block_on(file_guard::future::lock(&f, Lock::Shared, 0, 1))?;
+    assert!(g.is_shared());
+    drop(g);
+
+    let g = block_on(file_guard::future::lock_any(&f, 0, 1))?;
+    assert!(g.is_exclusive());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_future_wait() -> io::Result<()> {
+    use file_guard::{Backend, LockOptions, Wait};
+    use std::io::ErrorKind;
+    use std::mem;
+    use std::pin::Pin;
+    use std::task::Waker;
+    use std::time::Duration;
+
+    let a = open("test-future-wait")?;
+    let b = open("test-future-wait")?;
+
+    let mut opts = LockOptions::new();
+    opts.backend(Backend::OpenFile);
+
+    let g = opts.acquire(&a)?;
+
+    let e = block_on(file_guard::future::acquire(
+        opts.clone().wait(Wait::Timeout(Duration::from_millis(20))),
+        &b,
+    ))
+    .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::TimedOut);
+
+    // a cancelled acquisition must not leave a lock behind
+    let mut pending = file_guard::future::acquire(&opts, &b);
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(Pin::new(&mut pending).poll(&mut cx).is_pending());
+    drop(g);
+    thread::sleep(Duration::from_millis(20));
+    drop(pending);
+    let g = opts.clone().wait(Wait::Try).acquire(&a)?;
+
+    // a forgotten acquisition must not lock whichever file reuses its handle
+    let c = open("test-future-wait")?;
+    let mut pending = file_guard::future::acquire(&opts, &c);
+    assert!(Pin::new(&mut pending).poll(&mut cx).is_pending());
+    mem::forget(pending);
+    drop(c);
+    let other = open("test-future-wait-other")?;
+    drop(g);
+    thread::sleep(Duration::from_millis(20));
+    let probe = open("test-future-wait-other")?;
+    drop(opts.clone().wait(Wait::Try).acquire(&probe)?);
+    drop(other);
+    let g = opts.clone().wait(Wait::Try).acquire(&a)?;
+
+    thread::scope(|s| {
+        s.spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            drop(g);
+        });
+        let h = block_on(file_guard::future::acquire(opts.any(), &b))?;
+        assert!(h.is_exclusive());
+        Ok(())
+    })
+}
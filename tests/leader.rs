@@ -0,0 +1,67 @@
+use std::io::{self, ErrorKind};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use file_guard::LeaderElection;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_leader_election() -> io::Result<()> {
+    let path = "test-leader-election";
+    let f = open(path)?;
+    let other = open(path)?;
+    f.set_len(0)?;
+
+    let election = LeaderElection::new(&f, 4);
+    let follower = LeaderElection::new(&other, 4);
+    assert!(!follower.is_leader_alive()?);
+
+    let leader = election.wait_for_leadership()?;
+    assert_eq!(leader.token(), 1);
+    assert_eq!(leader.guard().range(), 4..12);
+    assert!(follower.is_leader_alive()?);
+
+    let e = follower.try_lead().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    // each new leader gets a greater token
+    drop(leader);
+    assert!(!follower.is_leader_alive()?);
+    let leader = follower.try_lead()?;
+    assert_eq!(leader.token(), 2);
+    drop(leader);
+    assert_eq!(election.wait_for_leadership()?.token(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn test_wait_for_leadership() -> io::Result<()> {
+    let path = "test-wait-for-leadership";
+    let f = open(path)?;
+    let election = LeaderElection::new(&f, 0);
+    let leader = election.wait_for_leadership()?;
+    let token = leader.token();
+
+    let (tx, rx) = mpsc::channel();
+    let follower = thread::spawn(move || -> io::Result<u64> {
+        let f = open(path)?;
+        let election = LeaderElection::new(&f, 0);
+        let leader = election.wait_for_leadership()?;
+        tx.send(()).unwrap();
+        Ok(leader.token())
+    });
+
+    // the follower waits for as long as the leader is alive
+    let e = rx.recv_timeout(Duration::from_millis(100)).unwrap_err();
+    assert_eq!(e, mpsc::RecvTimeoutError::Timeout);
+    drop(leader);
+
+    assert_eq!(follower.join().unwrap()?, token + 1);
+
+    Ok(())
+}
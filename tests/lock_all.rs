@@ -0,0 +1,88 @@
+use std::fs::File;
+use std::io::{self, ErrorKind};
+
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_lock_all() -> io::Result<()> {
+    let a = open("test-lock-all-a")?;
+    let b = open("test-lock-all-b")?;
+    let other = open("test-lock-all-b")?;
+
+    let forward = file_guard::lock_all([
+        (&a, Lock::Shared, 0..8),
+        (&b, Lock::Shared, 8..16),
+        (&b, Lock::Shared, 0..8),
+    ])?;
+    let reverse = file_guard::lock_all([
+        (&b, Lock::Shared, 0..8),
+        (&b, Lock::Shared, 8..16),
+        (&a, Lock::Shared, 0..8),
+    ])?;
+    let order = |set: &file_guard::GuardSet<&File>| -> Vec<_> {
+        set.iter()
+            .map(|g| (std::ptr::eq(**g, &a), g.range()))
+            .collect()
+    };
+    assert_eq!(order(&forward), order(&reverse));
+    assert_eq!(forward.len(), 3);
+
+    let e = file_guard::try_lock(&other, Lock::Exclusive, 0, 16).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    drop(forward);
+    drop(reverse);
+    drop(file_guard::try_lock(&other, Lock::Exclusive, 0, 16)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_lock_all_same_file() -> io::Result<()> {
+    let a = open("test-lock-all-same")?;
+    let b = open("test-lock-all-same")?;
+
+    let e =
+        file_guard::lock_all([(&a, Lock::Shared, 0..8), (&b, Lock::Shared, 4..12)]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let set = file_guard::lock_all([(&a, Lock::Exclusive, 8..16), (&b, Lock::Exclusive, 0..8)])?;
+    assert_eq!(set.get(0).unwrap().range(), 0..8);
+    assert!(std::ptr::eq(**set.get(0).unwrap(), &b));
+
+    Ok(())
+}
+
+#[test]
+fn test_try_lock_all_rollback() -> io::Result<()> {
+    let a = open("test-try-lock-all-a")?;
+    let b = open("test-try-lock-all-b")?;
+    let other_a = open("test-try-lock-all-a")?;
+    let other_b = open("test-try-lock-all-b")?;
+
+    let blocker_a = file_guard::lock(&other_a, Lock::Exclusive, 0, 1)?;
+    let blocker_b = file_guard::lock(&other_b, Lock::Exclusive, 0, 1)?;
+    let requests = || [(&a, Lock::Exclusive, 0..1), (&b, Lock::Exclusive, 0..1)];
+
+    // whichever file is ordered first, claiming the other must fail and
+    // release the first
+    drop(blocker_a);
+    let e = file_guard::try_lock_all(requests()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    drop(file_guard::try_lock(&other_a, Lock::Exclusive, 0, 1)?);
+
+    let blocker_a = file_guard::lock(&other_a, Lock::Exclusive, 0, 1)?;
+    drop(blocker_b);
+    let e = file_guard::try_lock_all(requests()).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    drop(file_guard::try_lock(&other_b, Lock::Exclusive, 0, 1)?);
+
+    drop(blocker_a);
+    let set = file_guard::try_lock_all(requests())?;
+    assert_eq!(set.len(), 2);
+
+    Ok(())
+}
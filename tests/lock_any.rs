@@ -1,8 +1,12 @@
 use std::fs::OpenOptions;
-use std::io;
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::Duration;
 
+mod common;
 mod pipeline;
 
+use common::open;
 use file_guard::Lock;
 
 #[test]
@@ -37,3 +41,47 @@ fn test_lock_any() -> io::Result<()> {
 
     pipeline::interleave(&mut a, &mut b)
 }
+
+#[test]
+fn test_lock_any_released() -> io::Result<()> {
+    let path = "test-lock-any-released";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    let g = file_guard::lock(&other, Lock::Exclusive, 0, 1)?;
+    thread::scope(|s| {
+        let t = s.spawn(|| file_guard::lock_any(&f, 0, 1).map(|g| g.lock_type()));
+        thread::sleep(Duration::from_millis(20));
+        drop(g);
+        // the whole lock became available at once, so a shared lock must not
+        // have been claimed while waiting
+        assert_eq!(t.join().unwrap().unwrap(), Lock::Exclusive);
+    });
+
+    Ok(())
+}
+
+#[test]
+fn test_try_lock_any() -> io::Result<()> {
+    let path = "test-try-lock-any";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    let mut g = file_guard::lock(&other, Lock::Exclusive, 0, 1)?;
+    let e = file_guard::try_lock_any(&f, 0, 1).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    g.downgrade()?;
+    assert_eq!(
+        file_guard::try_lock_any(&f, 0, 1)?.lock_type(),
+        Lock::Shared
+    );
+
+    drop(g);
+    assert_eq!(
+        file_guard::try_lock_any(&f, 0, 1)?.lock_type(),
+        Lock::Exclusive
+    );
+
+    Ok(())
+}
@@ -0,0 +1,71 @@
+use std::io::{self, ErrorKind};
+use std::ops::Bound;
+
+use file_guard::{Lock, LockOptions};
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_lock_file() -> io::Result<()> {
+    let f = open("test-lock-file")?;
+
+    let g = file_guard::lock_file(&f, Lock::Exclusive)?;
+    assert!(g.is_unbounded());
+    assert_eq!(g.range(), 0..u64::MAX);
+    assert_eq!(g.len(), u64::MAX);
+    assert_eq!(format!("{:?}", g), "FileGuard::Exclusive(0, ..)");
+    drop(g);
+
+    let mut g = LockOptions::new().range(16..).acquire(&f)?;
+    assert!(g.is_unbounded());
+    assert_eq!(g.range(), 16..u64::MAX);
+    assert_eq!(g.len(), u64::MAX - 16);
+    g.downgrade()?;
+    assert!(g.is_shared());
+    drop(g);
+
+    let g = LockOptions::new().range(4..=7).acquire(&f)?;
+    assert!(!g.is_unbounded());
+    assert_eq!(g.range(), 4..8);
+    drop(g);
+
+    let e = LockOptions::new()
+        .range((Bound::Excluded(7), Bound::Excluded(8)))
+        .acquire(&f)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_lock_file_conflict() -> io::Result<()> {
+    use file_guard::Backend;
+
+    let a = open("test-lock-file-conflict")?;
+    let b = open("test-lock-file-conflict")?;
+
+    let g = Backend::OpenFile.lock_file(&a, Lock::Shared)?;
+
+    let e = Backend::OpenFile
+        .try_lock(&b, Lock::Exclusive, 1 << 40, 1)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    let conflict = Backend::OpenFile.probe(&b, Lock::Exclusive, 1 << 40, 1)?;
+    assert_eq!(conflict.map(|c| c.range), Some(0..u64::MAX));
+
+    let e = Backend::OpenFile
+        .try_lock_file(&b, Lock::Exclusive)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    drop(g);
+
+    let g = Backend::OpenFile.try_lock_file(&b, Lock::Exclusive)?;
+    assert!(g.is_exclusive());
+
+    Ok(())
+}
@@ -0,0 +1,75 @@
+use std::io::{self, ErrorKind};
+
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_lock_many() -> io::Result<()> {
+    let path = "test-lock-many";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    let set = file_guard::lock_many(
+        &f,
+        &[
+            (Lock::Exclusive, 64..128),
+            (Lock::Shared, 0..1),
+            (Lock::Exclusive, 256..u64::MAX),
+        ],
+    )?;
+    assert_eq!(set.len(), 3);
+    let ranges: Vec<_> = set.iter().map(|g| g.range()).collect();
+    assert_eq!(ranges, [0..1, 64..128, 256..u64::MAX]);
+    assert!(set.get(0).unwrap().is_shared());
+    assert!(set.get(2).unwrap().is_unbounded());
+
+    let e = file_guard::try_lock(&other, Lock::Exclusive, 0, 1).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    drop(set);
+    drop(file_guard::try_lock(&other, Lock::Exclusive, 0, 1)?);
+    drop(file_guard::try_lock(&other, Lock::Exclusive, 1 << 40, 1)?);
+
+    Ok(())
+}
+
+#[test]
+fn test_lock_many_invalid() -> io::Result<()> {
+    let f = open("test-lock-many-invalid")?;
+
+    let e = file_guard::lock_many(&f, &[(Lock::Shared, 0..8), (Lock::Shared, 4..12)]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let e = file_guard::try_lock_many(&f, &[(Lock::Shared, 8..8)]).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let set = file_guard::lock_many(&f, &[(Lock::Shared, 4..8), (Lock::Shared, 0..4)])?;
+    assert_eq!(set.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn test_try_lock_many_rollback() -> io::Result<()> {
+    let path = "test-try-lock-many-rollback";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    let blocker = file_guard::lock(&other, Lock::Exclusive, 32, 8)?;
+    let e = file_guard::try_lock_many(&f, &[(Lock::Exclusive, 32..40), (Lock::Exclusive, 0..8)])
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    // the first range was claimed and then released by the failure
+    drop(file_guard::try_lock(&other, Lock::Exclusive, 0, 8)?);
+
+    drop(blocker);
+    let set = file_guard::try_lock_many(&f, &[(Lock::Exclusive, 32..40), (Lock::Exclusive, 0..8)])?;
+    let guards = set.into_guards();
+    assert_eq!(guards[0].range(), 0..8);
+    assert_eq!(guards[1].range(), 32..40);
+
+    Ok(())
+}
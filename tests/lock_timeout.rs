@@ -0,0 +1,54 @@
+use std::fs::OpenOptions;
+use std::io;
+use std::time::Duration;
+
+mod pipeline;
+
+use file_guard::Lock;
+
+#[test]
+fn test_lock_timeout() -> io::Result<()> {
+    let path = "test-lock-timeout";
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    f.set_len(1024)?;
+
+    let mut a = pipeline::Pipeline::new(path)
+        .lock(Lock::Exclusive, 0, 1)
+        .write(0, 1)
+        .wait(0, 2)
+        .unlock()
+        .write(0, 3)
+        .spawn("a")?;
+
+    let mut b = pipeline::Pipeline::new(path)
+        .wait(0, 1)
+        .lock_timeout(Err(Lock::Shared), 0, 1, 50)
+        .write(0, 2)
+        .wait(0, 3)
+        .lock_timeout(Ok(Lock::Exclusive), 0, 1, 50)
+        .unlock()
+        .spawn("b")?;
+
+    pipeline::interleave(&mut a, &mut b)
+}
+
+#[test]
+fn test_lock_timeout_max() -> io::Result<()> {
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open("test-lock-timeout-max")?;
+
+    // an unrepresentable deadline waits without one
+    let g = file_guard::lock_timeout(&f, Lock::Exclusive, 0, 1, Duration::MAX)?;
+    assert!(g.is_exclusive());
+
+    Ok(())
+}
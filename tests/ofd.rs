@@ -0,0 +1,57 @@
+#![cfg(target_os = "linux")]
+
+use std::io::{self, ErrorKind};
+use std::thread;
+
+use file_guard::{Backend, Lock};
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_ofd_lock() -> io::Result<()> {
+    let path = "test-ofd-lock";
+    let a = open(path)?;
+    let b = open(path)?;
+
+    let g = Backend::OpenFile.lock(&a, Lock::Exclusive, 0, 1)?;
+    assert_eq!(g.backend(), Backend::OpenFile);
+
+    // closing an unrelated descriptor does not drop the lock
+    drop(open(path)?);
+
+    let e = Backend::OpenFile
+        .try_lock(&b, Lock::Shared, 0, 1)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    let t = thread::spawn(move || -> io::Result<Lock> {
+        let g = Backend::OpenFile.lock(&b, Lock::Exclusive, 0, 1)?;
+        Ok(g.lock_type())
+    });
+
+    drop(g);
+    assert_eq!(t.join().unwrap()?, Lock::Exclusive);
+
+    Ok(())
+}
+
+#[test]
+fn test_ofd_downgrade() -> io::Result<()> {
+    let path = "test-ofd-downgrade";
+    let a = open(path)?;
+    let b = open(path)?;
+
+    let mut g = Backend::OpenFile.try_lock(&a, Lock::Exclusive, 0, 1)?;
+    let e = Backend::OpenFile
+        .try_lock(&b, Lock::Shared, 0, 1)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    g.downgrade()?;
+    let h = Backend::OpenFile.try_lock(&b, Lock::Shared, 0, 1)?;
+    assert!(h.is_shared());
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use file_guard::OnceFile;
+
+mod common;
+
+use common::open;
+
+const HEADER_LEN: u64 = OnceFile::<&File>::HEADER_LEN;
+
+fn write_data(mut file: &File, data: &[u8]) -> io::Result<()> {
+    file.set_len(HEADER_LEN)?;
+    file.seek(SeekFrom::Start(HEADER_LEN))?;
+    file.write_all(data)
+}
+
+fn read_data(mut file: &File) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    file.seek(SeekFrom::Start(HEADER_LEN))?;
+    file.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+#[test]
+fn test_once_file() -> io::Result<()> {
+    let path = "test-once-file";
+    let f = open(path)?;
+    let other = open(path)?;
+    f.set_len(0)?;
+
+    let lock = OnceFile::new(&f).get_or_init(|file| write_data(file, b"hello"))?;
+    assert!(lock.is_shared());
+    assert_eq!(lock.range(), HEADER_LEN..u64::MAX);
+
+    // the file is already initialized for every later caller
+    let lock = OnceFile::new(&other).get_or_init(|_| panic!("init called twice"))?;
+    assert_eq!(read_data(&lock)?, b"hello");
+
+    Ok(())
+}
+
+#[test]
+fn test_once_file_retry() -> io::Result<()> {
+    let path = "test-once-file-retry";
+    let f = open(path)?;
+    f.set_len(0)?;
+
+    let e = OnceFile::new(&f)
+        .get_or_init(|file| {
+            write_data(file, b"partial")?;
+            Err(ErrorKind::Other.into())
+        })
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::Other);
+
+    // a failed init leaves the file to be initialized again
+    let lock = OnceFile::new(&f).get_or_init(|file| write_data(file, b"done"))?;
+    assert_eq!(read_data(&lock)?, b"done");
+
+    Ok(())
+}
+
+#[test]
+fn test_once_file_threads() -> io::Result<()> {
+    let path = "test-once-file-threads";
+    open(path)?.set_len(0)?;
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let calls = calls.clone();
+            thread::spawn(move || -> io::Result<Vec<u8>> {
+                let f = open(path)?;
+                let lock = OnceFile::new(&f).get_or_init(|file| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    write_data(file, b"shared")
+                })?;
+                read_data(&lock)
+            })
+        })
+        .collect();
+
+    for t in threads {
+        assert_eq!(t.join().unwrap()?, b"shared");
+    }
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+    Ok(())
+}
@@ -0,0 +1,112 @@
+use std::io::{self, ErrorKind};
+
+use file_guard::{Backend, Lock, LockOptions, Wait};
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_options() -> io::Result<()> {
+    let f = open("test-options")?;
+
+    let g = LockOptions::new()
+        .lock(Lock::Shared)
+        .range(4..12)
+        .wait(Wait::Try)
+        .acquire(&f)?;
+    assert!(g.is_shared());
+    assert_eq!(g.range(), 4..12);
+    assert_eq!(g.backend(), Backend::Default);
+    drop(g);
+
+    let g = LockOptions::new().any().range(0..1).acquire(&f)?;
+    assert!(g.is_exclusive());
+    drop(g);
+
+    let e = LockOptions::new().range(1..1).acquire(&f).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_options_wait() -> io::Result<()> {
+    use std::time::Duration;
+
+    let a = open("test-options-wait")?;
+    let b = open("test-options-wait")?;
+
+    let mut opts = LockOptions::new();
+    opts.backend(Backend::OpenFile).range(0..8);
+
+    let g = opts.lock(Lock::Shared).acquire(&a)?;
+
+    let e = opts
+        .lock(Lock::Exclusive)
+        .wait(Wait::Try)
+        .acquire(&b)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    let e = opts
+        .wait(Wait::Timeout(Duration::from_millis(20)))
+        .acquire(&b)
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::TimedOut);
+
+    let h = opts.any().acquire(&b)?;
+    assert!(h.is_shared());
+    drop(h);
+
+    drop(g);
+    let h = opts
+        .wait(Wait::Backoff {
+            min: Duration::from_millis(1),
+            max: Duration::from_millis(10),
+        })
+        .acquire(&b)?;
+    assert!(h.is_exclusive());
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_options_backoff_zero() -> io::Result<()> {
+    use std::thread;
+    use std::time::Duration;
+
+    fn cpu_time() -> Duration {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    }
+
+    let a = open("test-options-backoff-zero")?;
+    let b = open("test-options-backoff-zero")?;
+
+    let mut opts = LockOptions::new();
+    opts.backend(Backend::OpenFile).wait(Wait::Backoff {
+        min: Duration::ZERO,
+        max: Duration::ZERO,
+    });
+
+    // a zero delay still sleeps between attempts rather than spinning
+    let g = opts.acquire(&a)?;
+    thread::scope(|s| {
+        s.spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            drop(g);
+        });
+        let start = cpu_time();
+        let h = opts.acquire(&b)?;
+        assert!(h.is_exclusive());
+        assert!(cpu_time() - start < Duration::from_millis(100));
+        Ok(())
+    })
+}
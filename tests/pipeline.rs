@@ -74,6 +74,31 @@ impl Pipeline {
         self.add_lock("trylock", lock, off, len)
     }
 
+    #[allow(dead_code)]
+    pub fn lock_timeout(&mut self, lock: Try, off: usize, len: usize, ms: usize) -> &mut Self {
+        self.add_lock("locktimeout", lock, off, len);
+        self.args.push(format!("{}", ms));
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn probe(
+        &mut self,
+        lock: Lock,
+        off: usize,
+        len: usize,
+        held: Option<(Lock, usize, usize)>,
+    ) -> &mut Self {
+        match held {
+            Some((held, start, end)) => self
+                .lines
+                .push(format!("+probe {:?} {} {}", held, start, end)),
+            None => self.lines.push("+probe None".to_string()),
+        }
+        self.add_arg_size2("probe", off, len);
+        self.add_arg_type(Ok(lock))
+    }
+
     #[allow(dead_code)]
     pub fn lock_any(&mut self, expect: Lock, off: usize, len: usize) -> &mut Self {
         self.add_lock_result("lockany", Ok(expect));
@@ -0,0 +1,38 @@
+use std::fs::OpenOptions;
+use std::io;
+
+mod pipeline;
+
+use file_guard::Lock;
+
+#[test]
+fn test_probe() -> io::Result<()> {
+    let path = "test-probe";
+    let f = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)?;
+    f.set_len(1024)?;
+
+    let mut a = pipeline::Pipeline::new(path)
+        .lock(Lock::Shared, 8, 4)
+        .write(0, 1)
+        .wait(0, 2)
+        .unlock()
+        .write(0, 3)
+        .spawn("a")?;
+
+    let mut b = pipeline::Pipeline::new(path)
+        .wait(0, 1)
+        .probe(Lock::Shared, 8, 1, None)
+        .probe(Lock::Exclusive, 10, 4, Some((Lock::Shared, 8, 12)))
+        .probe(Lock::Exclusive, 12, 1, None)
+        .write(0, 2)
+        .wait(0, 3)
+        .probe(Lock::Exclusive, 8, 4, None)
+        .spawn("b")?;
+
+    pipeline::interleave(&mut a, &mut b)
+}
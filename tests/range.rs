@@ -0,0 +1,33 @@
+use std::io::{self, ErrorKind};
+
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_large_range() -> io::Result<()> {
+    let f = open("test-large-range")?;
+
+    let off = 5 << 30;
+    let g = file_guard::lock(&f, Lock::Exclusive, off, 1 << 32)?;
+    assert_eq!(g.offset(), off);
+    assert_eq!(g.len(), 1 << 32);
+    assert_eq!(g.range(), off..off + (1 << 32));
+
+    Ok(())
+}
+
+#[test]
+fn test_overflow_range() -> io::Result<()> {
+    let f = open("test-overflow-range")?;
+
+    let e = file_guard::lock(&f, Lock::Exclusive, u64::MAX, 2).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let e = file_guard::try_lock(&f, Lock::Shared, u64::MAX - 1, u64::MAX).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+#![cfg(target_os = "linux")]
+
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::time::Duration;
+use std::{process, thread};
+
+use file_guard::os::unix::{set_deadlock_detection, FileGuardExt};
+use file_guard::{FileGuard, Lock};
+
+mod common;
+
+use common::{held, open};
+
+#[test]
+fn test_registry_overlap() -> io::Result<()> {
+    let path = "test-registry-overlap";
+    let f = open(path)?;
+    let observer = open(path)?;
+
+    let a = file_guard::lock(&f, Lock::Shared, 0, 10)?;
+    let b = file_guard::lock(&f, Lock::Shared, 5, 10)?;
+    drop(a);
+    assert_eq!(held(&observer, 0, 5)?, None);
+    assert_eq!(held(&observer, 5, 10)?, Some(Lock::Shared));
+    drop(b);
+    assert_eq!(held(&observer, 0, 15)?, None);
+
+    let a = file_guard::lock(&f, Lock::Shared, 0, 10)?;
+    let b = file_guard::lock_file(&f, Lock::Shared)?;
+    drop(b);
+    assert_eq!(held(&observer, 0, 10)?, Some(Lock::Shared));
+    assert_eq!(held(&observer, 10, 1 << 40)?, None);
+    drop(a);
+
+    Ok(())
+}
+
+#[test]
+fn test_registry_conflict() -> io::Result<()> {
+    let path = "test-registry-conflict";
+    let f = open(path)?;
+
+    let mut g = file_guard::lock(&f, Lock::Exclusive, 0, 4)?;
+
+    let e = file_guard::try_lock(&f, Lock::Shared, 2, 4).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    let conflict = file_guard::probe(&f, Lock::Shared, 2, 4)?.unwrap();
+    assert_eq!(conflict.lock, Lock::Exclusive);
+    assert_eq!(conflict.range, 0..4);
+    assert_eq!(conflict.pid, Some(process::id() as i32));
+
+    g.downgrade()?;
+    let h = file_guard::try_lock(&f, Lock::Shared, 2, 4)?;
+    drop(h);
+    assert_eq!(held(&open(path)?, 0, 4)?, Some(Lock::Shared));
+
+    thread::scope(|s| {
+        let t = s.spawn(|| file_guard::lock(&f, Lock::Exclusive, 3, 1).map(|g| g.range()));
+        thread::sleep(Duration::from_millis(20));
+        drop(g);
+        assert_eq!(t.join().unwrap().unwrap(), 3..4);
+    });
+
+    Ok(())
+}
+
+fn upgrade(mut g: FileGuard<&File>) -> Option<Lock> {
+    match g.upgrade() {
+        Ok(()) => Some(g.lock_type()),
+        Err(e) => {
+            assert_eq!(e.raw_os_error(), Some(libc::EDEADLK));
+            None
+        }
+    }
+}
+
+#[test]
+fn test_registry_upgrade_deadlock() -> io::Result<()> {
+    let path = "test-registry-upgrade";
+    let f = open(path)?;
+
+    let a = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let b = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+
+    let upgraded = thread::scope(|s| {
+        let a = s.spawn(move || upgrade(a));
+        let b = s.spawn(move || upgrade(b));
+        [a.join().unwrap(), b.join().unwrap()]
+    });
+    assert!(upgraded.contains(&Some(Lock::Exclusive)));
+    assert!(upgraded.contains(&None));
+
+    Ok(())
+}
+
+#[test]
+fn test_registry_upgrade_release() -> io::Result<()> {
+    let path = "test-registry-upgrade-release";
+    let f = open(path)?;
+
+    // both guards hold the same range, so only their ids tell them apart
+    let mut a = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let b = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+
+    thread::scope(|s| {
+        let t = s.spawn(move || a.upgrade().map(|_| a.lock_type()));
+        thread::sleep(Duration::from_millis(20));
+        drop(b);
+        assert_eq!(t.join().unwrap().unwrap(), Lock::Exclusive);
+    });
+    assert_eq!(held(&open(path)?, 0, 1)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_registry_same_thread() -> io::Result<()> {
+    let path = "test-registry-same-thread";
+    let f = open(path)?;
+
+    // a guard sent to another thread is waited on without detection
+    let g = file_guard::lock(&f, Lock::Exclusive, 0, 4)?;
+    thread::scope(|s| {
+        s.spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            drop(g);
+        });
+        file_guard::lock(&f, Lock::Shared, 2, 4).map(drop)
+    })?;
+
+    set_deadlock_detection(true);
+    let g = file_guard::lock(&f, Lock::Exclusive, 0, 4)?;
+    let e = file_guard::lock(&f, Lock::Shared, 2, 4).unwrap_err();
+    assert_eq!(e.raw_os_error(), Some(libc::EDEADLK));
+    let e = file_guard::try_lock(&f, Lock::Shared, 2, 4).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    drop(g);
+
+    let mut a = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let b = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    assert_eq!(a.upgrade().unwrap_err().raw_os_error(), Some(libc::EDEADLK));
+    drop(b);
+    a.upgrade()?;
+    assert!(a.is_exclusive());
+    set_deadlock_detection(false);
+
+    Ok(())
+}
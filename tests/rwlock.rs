@@ -0,0 +1,81 @@
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use file_guard::ProcessRwLock;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_process_rwlock() -> io::Result<()> {
+    let f = open("test-process-rwlock")?;
+    let lock = ProcessRwLock::new(&f, 0, 1);
+
+    let a = lock.read()?;
+    let b = lock.try_read()?;
+    assert_eq!(lock.try_write().unwrap_err().kind(), ErrorKind::WouldBlock);
+    drop(a);
+    assert_eq!(lock.try_write().unwrap_err().kind(), ErrorKind::WouldBlock);
+    drop(b);
+
+    let w = lock.try_write()?;
+    assert_eq!(lock.try_read().unwrap_err().kind(), ErrorKind::WouldBlock);
+    drop(w);
+
+    let count = AtomicUsize::new(0);
+    thread::scope(|s| {
+        for _ in 0..4 {
+            s.spawn(|| {
+                let _w = lock.write().unwrap();
+                assert_eq!(count.fetch_add(1, Ordering::SeqCst), 0);
+                thread::sleep(Duration::from_millis(5));
+                count.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    Ok(())
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn test_process_rwlock_file() -> io::Result<()> {
+    use file_guard::Lock;
+
+    let path = "test-process-rwlock-file";
+    let observer = open(path)?;
+    let held = || common::held(&observer, 0, 1);
+
+    let file = open(path)?;
+    let lock = ProcessRwLock::new(&file, 0, 1);
+
+    let a = lock.read()?;
+    let b = lock.read()?;
+    assert_eq!(held()?, Some(Lock::Shared));
+    drop(a);
+    assert_eq!(held()?, Some(Lock::Shared));
+    drop(b);
+    assert_eq!(held()?, None);
+
+    let w = lock.write()?;
+    assert_eq!(held()?, Some(Lock::Exclusive));
+    drop(w);
+    assert_eq!(held()?, None);
+
+    // a reader waiting for the file lock does not block an attempt to read
+    let g = file_guard::lock(&file, Lock::Exclusive, 0, 1)?;
+    thread::scope(|s| {
+        let t = s.spawn(|| lock.read().map(drop));
+        thread::sleep(Duration::from_millis(20));
+        let e = lock.try_read().unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::WouldBlock);
+        drop(g);
+        t.join().unwrap()
+    })?;
+    assert_eq!(held()?, None);
+
+    Ok(())
+}
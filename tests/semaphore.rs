@@ -0,0 +1,64 @@
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+use file_guard::FileSemaphore;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_semaphore() -> io::Result<()> {
+    let path = "test-semaphore";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    let sem = FileSemaphore::new(&f, 16, 2);
+    assert_eq!(sem.permits(), 2);
+
+    let a = sem.acquire()?;
+    let b = sem.try_acquire()?;
+    assert_eq!(a.index(), 0);
+    assert_eq!(b.index(), 1);
+    assert_eq!(b.guard().range(), 17..18);
+
+    let e = sem.try_acquire().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    let e = sem.acquire_timeout(Duration::from_millis(20)).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::TimedOut);
+
+    // another process, or another open of the file, sees the same permits
+    let e = FileSemaphore::new(&other, 16, 2).try_acquire().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    drop(a);
+    let c = sem.try_acquire()?;
+    assert_eq!(c.index(), 0);
+
+    thread::scope(|s| {
+        let t = s.spawn(|| sem.acquire().map(|p| p.index()));
+        thread::sleep(Duration::from_millis(20));
+        drop(b);
+        assert_eq!(t.join().unwrap().unwrap(), 1);
+    });
+
+    // a timeout too large for a deadline waits without one
+    let d = sem.acquire_timeout(Duration::MAX)?;
+    assert_eq!((c.index(), d.index()), (0, 1));
+
+    Ok(())
+}
+
+#[test]
+fn test_semaphore_invalid() -> io::Result<()> {
+    let f = open("test-semaphore-invalid")?;
+
+    let e = FileSemaphore::new(&f, 0, 0).try_acquire().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let e = FileSemaphore::new(&f, u64::MAX, 2).acquire().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
@@ -0,0 +1,97 @@
+#![cfg(target_os = "linux")]
+
+use std::io::{self, ErrorKind};
+
+use file_guard::{Backend, Lock};
+
+mod common;
+
+use common::{held, open};
+
+#[test]
+fn test_shrink() -> io::Result<()> {
+    let path = "test-shrink";
+    let f = open(path)?;
+    let observer = open(path)?;
+
+    let mut g = file_guard::lock(&f, Lock::Exclusive, 0, 64)?;
+    g.shrink_to(16..48)?;
+    assert_eq!(g.range(), 16..48);
+    assert_eq!(held(&observer, 0, 16)?, None);
+    assert_eq!(held(&observer, 16, 32)?, Some(Lock::Exclusive));
+    assert_eq!(held(&observer, 48, 16)?, None);
+
+    let e = g.shrink_to(0..32).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+    let e = g.release_prefix(32).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    g.release_prefix(8)?;
+    assert_eq!(g.range(), 24..48);
+    assert_eq!(held(&observer, 16, 8)?, None);
+
+    // a failed split keeps the whole lock
+    let e = g.split_at(24).unwrap_err();
+    assert_eq!(e.error().kind(), ErrorKind::InvalidInput);
+    let g = e.into_guard();
+    assert_eq!(held(&observer, 24, 24)?, Some(Lock::Exclusive));
+
+    let (head, tail) = g.split_at(8)?;
+    assert_eq!(head.range(), 24..32);
+    assert_eq!(tail.range(), 32..48);
+    drop(head);
+    assert_eq!(held(&observer, 24, 8)?, None);
+    assert_eq!(held(&observer, 32, 16)?, Some(Lock::Exclusive));
+    drop(tail);
+    assert_eq!(held(&observer, 0, 64)?, None);
+
+    Ok(())
+}
+
+#[test]
+fn test_shrink_overlap() -> io::Result<()> {
+    let path = "test-shrink-overlap";
+    let f = open(path)?;
+    let observer = open(path)?;
+
+    let mut a = file_guard::lock(&f, Lock::Shared, 0, 10)?;
+    let b = file_guard::lock(&f, Lock::Shared, 5, 10)?;
+    a.shrink_to(..3)?;
+    assert_eq!(held(&observer, 3, 2)?, None);
+    assert_eq!(held(&observer, 5, 5)?, Some(Lock::Shared));
+    drop(b);
+    assert_eq!(held(&observer, 3, 12)?, None);
+    assert_eq!(held(&observer, 0, 3)?, Some(Lock::Shared));
+
+    Ok(())
+}
+
+#[test]
+fn test_shrink_unbounded() -> io::Result<()> {
+    let path = "test-shrink-unbounded";
+    let f = open(path)?;
+    let observer = open(path)?;
+
+    let mut g = file_guard::lock_file(&f, Lock::Exclusive)?;
+    g.release_prefix(100)?;
+    assert!(g.is_unbounded());
+    assert_eq!(g.range(), 100..u64::MAX);
+    assert_eq!(held(&observer, 0, 100)?, None);
+
+    let (head, tail) = g.split_at(10)?;
+    assert_eq!(head.range(), 100..110);
+    assert!(tail.is_unbounded());
+    assert_eq!(format!("{:?}", tail), "FileGuard::Exclusive(110, ..)");
+    drop(tail);
+    assert_eq!(held(&observer, 110, 1 << 40)?, None);
+    assert_eq!(held(&observer, 100, 10)?, Some(Lock::Exclusive));
+
+    let a = open(path)?;
+    let mut g = Backend::OpenFile.lock(&a, Lock::Shared, 0, 16)?;
+    g.shrink_to(4..8)?;
+    assert_eq!(g.range(), 4..8);
+    let conflict = Backend::OpenFile.probe(&observer, Lock::Exclusive, 0, 16)?;
+    assert_eq!(conflict.map(|c| c.range), Some(4..8));
+
+    Ok(())
+}
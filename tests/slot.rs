@@ -0,0 +1,38 @@
+use std::io::{self, ErrorKind};
+
+use file_guard::SlotAllocator;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_slot_allocator() -> io::Result<()> {
+    let path = "test-slot-allocator";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    let slots = SlotAllocator::new(&f, 8, 3);
+    assert_eq!(slots.slots(), 3);
+    assert!(slots.claimed()?.is_empty());
+
+    let a = slots.try_claim()?;
+    let b = slots.claim()?;
+    let c = slots.try_claim()?;
+    assert_eq!([a.index(), b.index(), c.index()], [0, 1, 2]);
+    assert_eq!(c.guard().range(), 10..11);
+    assert_eq!(slots.claimed()?, [0, 1, 2]);
+
+    let e = slots.try_claim().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    // the lowest free slot is reused first
+    drop(b);
+    drop(a);
+    assert_eq!(SlotAllocator::new(&other, 8, 3).claimed()?, [2]);
+    let d = slots.try_claim()?;
+    assert_eq!(d.index(), 0);
+    assert_eq!(slots.claimed()?, [0, 2]);
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+#![cfg(feature = "tokio")]
+
+use std::io;
+
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+#[tokio::test]
+async fn test_tokio_lock() -> io::Result<()> {
+    let f = open("test-tokio-lock")?;
+
+    let g = file_guard::tokio::lock(&f, Lock::Shared, 0, 1).await?;
+    assert!(g.is_shared());
+    drop(g);
+
+    let g = file_guard::tokio::lock_any(&f, 0, 1).await?;
+    assert!(g.is_exclusive());
+
+    Ok(())
+}
+
+#[tokio::test]
+#[cfg(target_os = "linux")]
+async fn test_tokio_cancel() -> io::Result<()> {
+    use file_guard::{Backend, LockOptions, Wait};
+    use std::io::ErrorKind;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let a = open("test-tokio-cancel")?;
+    let b = Arc::new(open("test-tokio-cancel")?);
+
+    let mut opts = LockOptions::new();
+    opts.backend(Backend::OpenFile);
+
+    let g = opts.acquire(&a)?;
+
+    let e = file_guard::tokio::acquire(opts.clone().wait(Wait::Try), b.clone())
+        .await
+        .unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    let pending = file_guard::tokio::acquire(&opts, b.clone());
+    assert!(tokio::time::timeout(Duration::from_millis(20), pending)
+        .await
+        .is_err());
+    drop(g);
+
+    // the cancelled acquisition must not have left a lock behind
+    let g = opts.acquire(&a)?;
+
+    let task = tokio::spawn(async move {
+        let g = file_guard::tokio::acquire(&opts, b).await?;
+        Ok::<_, io::Error>(g.lock_type())
+    });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    drop(g);
+    assert_eq!(task.await.unwrap()?, Lock::Exclusive);
+
+    Ok(())
+}
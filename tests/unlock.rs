@@ -0,0 +1,29 @@
+use std::io;
+use std::rc::Rc;
+
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_unlock() -> io::Result<()> {
+    let f = Rc::new(open("test-unlock")?);
+
+    let g = file_guard::lock(f, Lock::Exclusive, 0, 1)?;
+    let f = g.unlock()?;
+
+    let g = file_guard::try_lock(&*f, Lock::Exclusive, 0, 1)?;
+    let file = g.unlock().map_err(io::Error::from)?;
+    assert!(file.metadata()?.is_file());
+
+    assert!(file_guard::take_unlock_error_hook().is_none());
+    file_guard::set_unlock_error_hook(Box::new(|e, lock, range| {
+        panic!("unexpected {} {:?} unlock error: {}", lock, range, e)
+    }));
+    drop(file_guard::lock(f, Lock::Shared, 0, 1)?);
+    assert!(file_guard::take_unlock_error_hook().is_some());
+
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+use file_guard::Lock;
+
+mod common;
+
+use common::open;
+
+#[test]
+fn test_upgradable() -> io::Result<()> {
+    let path = "test-upgradable";
+    let f = open(path)?;
+    let other = open(path)?;
+
+    let mut g = file_guard::lock_upgradable(&f, 0, 8)?;
+    assert_eq!(g.lock_type(), Lock::Shared);
+    assert_eq!(g.range(), 0..8);
+    assert_eq!(g.intent_offset(), 8);
+
+    let e = file_guard::try_lock_upgradable(&other, 0, 8).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    let reader = file_guard::try_lock(&other, Lock::Shared, 0, 8)?;
+    let e = g.try_upgrade().unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    assert_eq!(g.lock_type(), Lock::Shared);
+    drop(reader);
+
+    g.try_upgrade()?;
+    assert!(g.is_exclusive());
+    let e = file_guard::try_lock(&other, Lock::Shared, 0, 8).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    g.downgrade()?;
+    drop(file_guard::try_lock(&other, Lock::Shared, 0, 8)?);
+    let e = file_guard::try_lock_upgradable(&other, 0, 8).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+
+    drop(g);
+    drop(file_guard::try_lock_upgradable(&other, 0, 8)?);
+
+    Ok(())
+}
+
+fn write(path: &str) -> io::Result<Lock> {
+    let f = open(path)?;
+    let mut g = file_guard::lock_upgradable(&f, 0, 1)?;
+    thread::sleep(Duration::from_millis(10));
+    g.upgrade()?;
+    Ok(g.lock_type())
+}
+
+#[test]
+fn test_upgradable_concurrent() -> io::Result<()> {
+    let path = "test-upgradable-concurrent";
+
+    let upgraded = thread::scope(|s| {
+        let a = s.spawn(|| write(path));
+        let b = s.spawn(|| write(path));
+        [a.join().unwrap(), b.join().unwrap()]
+    });
+    for lock in upgraded {
+        assert_eq!(lock?, Lock::Exclusive);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_upgradable_invalid() -> io::Result<()> {
+    let f = open("test-upgradable-invalid")?;
+
+    let e = file_guard::lock_upgradable(&f, 0, 0).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    let e = file_guard::lock_upgradable(&f, u64::MAX - 1, 1).unwrap_err();
+    assert_eq!(e.kind(), ErrorKind::InvalidInput);
+
+    Ok(())
+}
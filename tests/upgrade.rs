@@ -0,0 +1,98 @@
+#![cfg(unix)]
+
+use std::fs::File;
+use std::io::{self, ErrorKind};
+use std::thread;
+use std::time::Duration;
+
+use file_guard::os::unix::FileGuardExt;
+use file_guard::{FileGuard, Lock, Wait};
+
+mod common;
+
+use common::open;
+
+fn upgrade_with_retry(g: FileGuard<&File>) -> io::Result<Lock> {
+    let g = g.upgrade_with_retry(Wait::Block)?;
+    thread::sleep(Duration::from_millis(10));
+    Ok(g.lock_type())
+}
+
+#[test]
+fn test_upgrade_with_retry() -> io::Result<()> {
+    let f = open("test-upgrade-with-retry")?;
+
+    let a = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let b = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+
+    let upgraded = thread::scope(|s| {
+        let a = s.spawn(move || upgrade_with_retry(a));
+        let b = s.spawn(move || upgrade_with_retry(b));
+        [a.join().unwrap(), b.join().unwrap()]
+    });
+    for lock in upgraded {
+        assert_eq!(lock?, Lock::Exclusive);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_upgrade_with_retry_try() -> io::Result<()> {
+    let f = open("test-upgrade-with-retry-try")?;
+
+    let a = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let b = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+
+    let (e, a) = a.upgrade_with_retry(Wait::Try).unwrap_err().into_parts();
+    assert_eq!(e.kind(), ErrorKind::WouldBlock);
+    assert!(!file_guard::is_deadlock(&e));
+    let a = a.unwrap();
+    assert!(a.is_shared());
+
+    drop(b);
+    let a = a.upgrade_with_retry(Wait::Try)?;
+    assert!(a.is_exclusive());
+
+    Ok(())
+}
+
+#[test]
+fn test_upgrade_with_retry_timeout() -> io::Result<()> {
+    let f = open("test-upgrade-with-retry-timeout")?;
+
+    // attempts never block, so two upgrades under a deadline still succeed
+    let a = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let b = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let upgraded = thread::scope(|s| {
+        let retry = |g: FileGuard<&File>| -> io::Result<Lock> {
+            let g = g.upgrade_with_retry(Wait::Timeout(Duration::from_secs(10)))?;
+            thread::sleep(Duration::from_millis(10));
+            Ok(g.lock_type())
+        };
+        let a = s.spawn(move || retry(a));
+        let b = s.spawn(move || retry(b));
+        [a.join().unwrap(), b.join().unwrap()]
+    });
+    for lock in upgraded {
+        assert_eq!(lock?, Lock::Exclusive);
+    }
+
+    // a reader that is never released times the upgrade out
+    let a = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let b = file_guard::lock(&f, Lock::Shared, 0, 1)?;
+    let (e, a) = a
+        .upgrade_with_retry(Wait::Timeout(Duration::from_millis(50)))
+        .unwrap_err()
+        .into_parts();
+    assert_eq!(e.kind(), ErrorKind::TimedOut);
+    let a = a.unwrap();
+    assert!(a.is_shared());
+
+    // the shared lock is still held after the timeout
+    drop(b);
+    let conflict = file_guard::probe(&f, Lock::Exclusive, 0, 1)?.unwrap();
+    assert_eq!(conflict.lock, Lock::Shared);
+
+    Ok(())
+}